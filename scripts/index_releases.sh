@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+# indexes upstream release tags of openzeppelin 3.x-5.x, solmate and solady into
+# ./library_db.json and ./library_cache of working directory, like index_library does
+#
+# usage: scripts/index_releases.sh [path to iscp binary] (run where you run iscp, needs git and network)
+
+set -euo pipefail
+
+ISCP="$(realpath "${1:-./iscp}")"
+WORK="$(mktemp -d)"
+trap 'rm -rf "$WORK"' EXIT
+
+# library name, repository, tag regex, package root inside checkout
+RELEASES=(
+    "openzeppelin-contracts https://github.com/OpenZeppelin/openzeppelin-contracts ^v[345]\.[0-9]+\.[0-9]+$ contracts"
+    "openzeppelin-contracts-upgradeable https://github.com/OpenZeppelin/openzeppelin-contracts-upgradeable ^v[345]\.[0-9]+\.[0-9]+$ contracts"
+    "solmate https://github.com/transmissions11/solmate ^v[0-9]+(\.[0-9]+)*$ src"
+    "solady https://github.com/Vectorized/solady ^v[0-9]+\.[0-9]+\.[0-9]+$ src"
+)
+
+for entry in "${RELEASES[@]}"; do
+    read -r name repo pattern package <<< "$entry"
+    for tag in $(git ls-remote --tags --refs "$repo" | sed 's|.*refs/tags/||' | grep -E "$pattern"); do
+        git clone --quiet --branch "$tag" --depth 1 "$repo" "$WORK/$name-$tag"
+        if [ -d "$WORK/$name-$tag/$package" ]; then
+            "$ISCP" index_library "$name" "$tag" "$WORK/$name-$tag/$package"
+        fi
+        rm -rf "$WORK/$name-$tag"
+    done
+done
+
+echo "./library_db.json has been updated!"
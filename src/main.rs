@@ -1,13 +1,30 @@
-use std::error::Error;
-use clap::{Arg, Command};
+use std::path::Path;
+
+use clap::{Arg, ArgAction, ArgMatches, Command};
 
 mod parser;
 use parser::ApiDB;
+use parser::ParseError;
+use parser::ParseOptions;
+use parser::ProviderKind;
+use parser::FilterConfig;
+use parser::SourceFilter;
+use parser::LibraryDB;
+use parser::OutputLayout;
+use parser::LIBRARY_CACHE_DIR;
+use parser::RetryPolicy;
 use parser::Parser;
 use parser::ParserMode;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() {
+    if let Err(why) = run().await {
+        eprintln!("{}", why);
+        std::process::exit(why.exit_code());
+    }
+}
+
+async fn run() -> Result<(), ParseError> {
 
     // init api database
 
@@ -30,6 +47,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     Arg::new("url")
                         .help("smart contract direct url e.g. \"https://etherscan.io/token/0xdac17f958d2ee523a2206206994597c13d831ec7\"")
                 )
+                .args(parse_args())
         )
         .subcommand(
             Command::new("parse_imm")
@@ -44,8 +62,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 )
                 .arg(
                     Arg::new("concurrent requests limit")
-                        .help("how many api requests at the same time")
+                        .help("optional cap of api requests at the same time per chain, pace is set by each chain rps")
                 )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .help("fetch every contract again instead of resuming from run.json")
+                )
+                .args(parse_args())
         )
         .subcommand(
             Command::new("change_api_key")
@@ -71,12 +96,52 @@ async fn main() -> Result<(), Box<dyn Error>> {
                         .help("your new api url")
                 )
         )
+        .subcommand(
+            Command::new("change_api_rpc")
+                .about("change json-rpc endpoint used to read proxy storage slots, empty url removes it")
+                .arg(
+                    Arg::new("name")
+                        .help("chain name(used as db key)")
+                )
+                .arg(
+                    Arg::new("rpc url")
+                        .help("json-rpc url e.g. \"https://eth.llamarpc.com\"")
+                )
+        )
+        .subcommand(
+            Command::new("change_api_rps")
+                .about("change requests per second budget of api key in api database")
+                .arg(
+                    Arg::new("name")
+                        .help("chain name(used as db key)")
+                )
+                .arg(
+                    Arg::new("rps")
+                        .help("allowed api requests per second e.g. 5 for free etherscan plan")
+                )
+        )
+        .subcommand(
+            Command::new("enable_api")
+                .about("enable chain in api database")
+                .arg(
+                    Arg::new("name")
+                        .help("chain name(used as db key)")
+                )
+        )
+        .subcommand(
+            Command::new("disable_api")
+                .about("disable chain in api database without removing it")
+                .arg(
+                    Arg::new("name")
+                        .help("chain name(used as db key)")
+                )
+        )
         .subcommand(
             Command::new("add_api")
                 .about("add new api to database")
                 .arg(
                     Arg::new("name")
-                        .help("chain name(as substring of direct contract url e.g. \"etherscan\" name and \"https://etherscan.io/0xfffffff\" url")
+                        .help("chain name(used as db key)")
                 )
                 .arg(
                     Arg::new("key")
@@ -84,7 +149,45 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 )
                 .arg(
                     Arg::new("api url")
-                        .help("your api url e.g. \"https://api.etherscan.io\"")
+                        .help("your api url e.g. \"https://api.etherscan.io\", optional for chains from \"chains list\"")
+                )
+                .arg(
+                    Arg::new("hosts")
+                        .num_args(0..)
+                        .help("explorer hosts of contract urls e.g. \"etherscan.io\", \"*.etherscan.io\" for subdomains, derived from api url if omitted")
+                )
+        )
+        .subcommand(
+            Command::new("change_v2_key")
+                .about("set etherscan multichain(v2) api key used for every chain it supports, empty key removes it")
+                .arg(
+                    Arg::new("key")
+                        .help("your etherscan api key")
+                )
+        )
+        .subcommand(
+            Command::new("chains")
+                .about("inspect chains known to api database")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("list")
+                        .about("list built-in and user chains with their hosts and key status")
+                )
+        )
+        .subcommand(
+            Command::new("index_library")
+                .about("fingerprint a local checkout of a library release into ./library_db.json and cache it for diffs")
+                .arg(
+                    Arg::new("library")
+                        .help("library name e.g. \"openzeppelin-contracts\", \"solmate\", \"solady\"")
+                )
+                .arg(
+                    Arg::new("version")
+                        .help("release version e.g. \"4.9.0\"")
+                )
+                .arg(
+                    Arg::new("dir")
+                        .help("package root of the release e.g. \"openzeppelin-contracts/contracts\"")
                 )
         )
         .subcommand(
@@ -105,26 +208,34 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 let mode = ParserMode::Single;
                 let url = arg.get_one::<String>("url").unwrap();
                 println!("\n### Parsing started! ###\n");
-                Parser::parse_contract(url, &db, &mode, false).await?;
+                let options = get_options(arg).await?;
+                Parser::parse_contract(url, &db, &mode, &options).await?;
                 println!("\n### Parsing finished! ###");
                 Ok(())
             } else {
-                Err("not all args were provided".into())
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
             }
         }
         Some(("parse_imm", args)) => {
             if args.contains_id("immunefi url") 
-            && args.contains_id("folder name") 
-            && args.contains_id("concurrent requests limit")  {
+            && args.contains_id("folder name") {
                 let url = args.get_one::<String>("immunefi url").unwrap();
                 let folder_name = args.get_one::<String>("folder name").unwrap();
-                let limit = args.get_one::<String>("concurrent requests limit").unwrap().parse::<usize>()?;
+                let limit = match args.get_one::<String>("concurrent requests limit") {
+                    Some(limit) => Some(limit.parse::<usize>()
+                        .map_err(|why| ParseError::InvalidArgs(format!("concurrent requests limit: {}", why)))?),
+                    None => None
+                };
                 println!("\n### Parsing started! ###\n");
-                Parser::immunefi_traverse(url, &db, false, folder_name, limit).await?;
+                let options = ParseOptions {
+                    force: args.get_flag("force"),
+                    ..get_options(args).await?
+                };
+                Parser::immunefi_traverse(url, &db, folder_name, limit, &options).await?;
                 println!("\n### Parsing finished! ###");
                 Ok(())
             } else {
-                Err("not all args were provided".into())
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
             }
         }
         Some(("change_api_key", args)) => {
@@ -137,7 +248,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!("changed api key to \"{}\" for name \"{}\"", new_key, name);
                 Ok(())
             } else {
-                Err("not all args were provided".into())
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
             }
         }
         Some(("change_api_url", args)) => {
@@ -150,22 +261,65 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!("changed api url to \"{}\" for name \"{}\"", new_url, name);
                 Ok(())
             } else {
-                Err("not all args were provided".into())
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
+            }
+        }
+        Some(("change_api_rpc", args)) => {
+            if args.contains_id("name") 
+            && args.contains_id("rpc url") {
+                let name = args.get_one::<String>("name").unwrap();
+                let rpc_url = args.get_one::<String>("rpc url").unwrap();
+                db.change_api_rpc(name, rpc_url).await?;
+                println!("### Database updated! ###\n");
+                println!("changed rpc url to \"{}\" for name \"{}\"", rpc_url, name);
+                Ok(())
+            } else {
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
+            }
+        }
+        Some(("change_api_rps", args)) => {
+            if args.contains_id("name") 
+            && args.contains_id("rps") {
+                let name = args.get_one::<String>("name").unwrap();
+                let rps = args.get_one::<String>("rps").unwrap()
+                    .parse::<f64>()
+                    .map_err(|why| ParseError::InvalidArgs(format!("rps: {}", why)))?;
+                db.change_api_rps(name, rps).await?;
+                println!("### Database updated! ###\n");
+                println!("changed api rps to {} for name \"{}\"", rps, name);
+                Ok(())
+            } else {
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
+            }
+        }
+        Some((command @ ("enable_api" | "disable_api"), arg)) => {
+            if arg.contains_id("name") {
+                let name = arg.get_one::<String>("name").unwrap();
+                let enabled = command == "enable_api";
+                db.change_api_enabled(name, enabled).await?;
+                println!("### Database updated! ###\n");
+                println!("{} {} api", if enabled { "enabled" } else { "disabled" }, name);
+                Ok(())
+            } else {
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
             }
         }
         Some(("add_api", args)) => {
             if args.contains_id("name") 
-            && args.contains_id("key") 
-            && args.contains_id("api url") {
+            && args.contains_id("key") {
                 let name = args.get_one::<String>("name").unwrap();
                 let key = args.get_one::<String>("key").unwrap();
-                let api_url = args.get_one::<String>("api url").unwrap();
-                db.add_new_api(name, key, api_url).await?;
+                let api_url = args.get_one::<String>("api url").map(String::as_str);
+                let hosts = args.get_many::<String>("hosts")
+                    .map(|hosts| hosts.cloned().collect::<Vec<_>>())
+                    .unwrap_or_default();
+                db.add_new_api(name, key, api_url, &hosts).await?;
                 println!("### Database updated! ###\n");
-                println!("added new name: {},\nkey: {},\napi url: {}", name, key, api_url);
+                println!("added new name: {},\nkey: {},\napi url: {},\nhosts: {}", 
+                    name, key, db.db[name].api_url, db.db[name].host_patterns().join(", "));
                 Ok(())
             } else {
-                Err("not all args were provided".into())
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
             }
         }
         Some(("remove_api", arg)) => {
@@ -176,9 +330,145 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!("removed {} api from database", name);
                 Ok(())
             } else {
-                Err("not all args were provided".into())
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
+            }
+        }
+        Some(("change_v2_key", arg)) => {
+            if arg.contains_id("key") {
+                let key = arg.get_one::<String>("key").unwrap();
+                db.change_v2_key(key).await?;
+                println!("### Database updated! ###\n");
+                match key.is_empty() {
+                    true => println!("removed etherscan v2 key"),
+                    false => println!("changed etherscan v2 key to \"{}\"", key)
+                }
+                Ok(())
+            } else {
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
+            }
+        }
+        Some(("index_library", args)) => {
+            if args.contains_id("library") 
+            && args.contains_id("version") 
+            && args.contains_id("dir") {
+                let library = args.get_one::<String>("library").unwrap();
+                let version = args.get_one::<String>("version").unwrap();
+                let dir = args.get_one::<String>("dir").unwrap();
+                let release = LibraryDB::index_release(library, version, Path::new(dir), Path::new(LIBRARY_CACHE_DIR)).await?;
+                println!("indexed {} files of {} {}", release.files.len(), release.name, release.version);
+                Ok(())
+            } else {
+                Err(ParseError::InvalidArgs("not all args were provided".to_owned()))
             }
         }
+        Some(("chains", args)) => match args.subcommand() {
+            Some(("list", _)) => {
+                println!("{:<14} {:>9} {:<8} {:<6} {:<36} hosts", "name", "chain id", "key", "rps", "api url");
+                for chain in db.chains() {
+                    let key = match (chain.api_keys.is_empty(), db.etherscan_v2_for(chain)) {
+                        (false, _) => "set",
+                        (true, Some(_)) => "v2",
+                        (true, None) => "missing"
+                    };
+                    println!("{:<14} {:>9} {:<8} {:<6} {:<36} {}{}", 
+                        chain.name,
+                        chain.chain_id.map(|id| id.to_string()).unwrap_or_default(),
+                        key,
+                        chain.rate_limit,
+                        chain.api_url,
+                        chain.host_patterns().join(", "),
+                        if chain.enabled { "" } else { " (disabled)" }
+                    );
+                }
+                Ok(())
+            }
+            _ => unreachable!()
+        }
         _ => unreachable!()
     } 
 }
+
+// optional args shared by parse commands
+
+fn parse_args() -> Vec<Arg> {
+    vec![
+        Arg::new("retries")
+            .long("retries")
+            .default_value("5")
+            .help("max attempts per api request when explorer is rate limiting"),
+        Arg::new("providers")
+            .long("providers")
+            .default_value("etherscan,sourcify")
+            .help("comma separated source providers tried in order: etherscan, sourcify"),
+        Arg::new("no proxy")
+            .long("no-proxy")
+            .action(ArgAction::SetTrue)
+            .help("don't fetch implementation sources of proxy contracts"),
+        Arg::new("layout")
+            .long("layout")
+            .default_value("plain")
+            .help("output layout of every contract folder: plain, foundry, hardhat"),
+        Arg::new("include")
+            .long("include")
+            .action(ArgAction::Append)
+            .help("save only source paths matching this glob, e.g. \"contracts/**\" (repeatable)"),
+        Arg::new("exclude")
+            .long("exclude")
+            .action(ArgAction::Append)
+            .help("skip source paths matching this glob, e.g. \"lib/forge-std/**\" (repeatable)"),
+        Arg::new("filters")
+            .long("filters")
+            .help("json file with \"include\" and \"exclude\" glob lists"),
+        Arg::new("all sources")
+            .long("all-sources")
+            .action(ArgAction::SetTrue)
+            .help("don't skip @openzeppelin libraries and import.sol files by default, implied by foundry and hardhat layouts"),
+        Arg::new("dedup")
+            .long("dedup")
+            .action(ArgAction::SetTrue)
+            .help("keep identical source files once in shared lib/ folder and symlink them"),
+        Arg::new("diff libraries")
+            .long("diff-libraries")
+            .action(ArgAction::SetTrue)
+            .help("write unified diffs of modified library files against closest cached release")
+    ]
+}
+
+// default excludes, then --filters file, then --include/--exclude flags, 
+// foundry and hardhat projects keep every source so they compile
+
+fn get_filter(args: &ArgMatches, layout: OutputLayout) -> Result<SourceFilter, ParseError> {
+    let mut config = match args.get_one::<String>("filters") {
+        Some(path) => serde_json::from_str::<FilterConfig>(&std::fs::read_to_string(path)?)?,
+        None => FilterConfig::default()
+    };
+    if !args.get_flag("all sources") && layout == OutputLayout::Plain {
+        let defaults = SourceFilter::DEFAULT_EXCLUDE.iter().map(|glob| glob.to_string());
+        config.exclude.splice(0..0, defaults);
+    }
+    config.include.extend(args.get_many::<String>("include").into_iter().flatten().cloned());
+    config.exclude.extend(args.get_many::<String>("exclude").into_iter().flatten().cloned());
+    SourceFilter::new(&config.include, &config.exclude)
+}
+
+async fn get_options(args: &ArgMatches) -> Result<ParseOptions, ParseError> {
+    let retries = args.get_one::<String>("retries").unwrap()
+        .parse::<u32>()
+        .map_err(|why| ParseError::InvalidArgs(format!("retries: {}", why)))?;
+    let providers = args.get_one::<String>("providers").unwrap()
+        .split(',')
+        .map(str::parse::<ProviderKind>)
+        .collect::<Result<Vec<_>, _>>()?;
+    let layout = args.get_one::<String>("layout").unwrap().parse()?;
+    Ok(ParseOptions {
+        retry: RetryPolicy::with_attempts(retries),
+        providers,
+        follow_proxies: !args.get_flag("no proxy"),
+        layout,
+        filter: get_filter(args, layout)?,
+        dedup: args.get_flag("dedup"),
+        libraries: LibraryDB::load().await?,
+        diff_libraries: args.get_flag("diff libraries"),
+        force: false
+    })
+}
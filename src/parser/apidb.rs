@@ -1,13 +1,43 @@
 use std::collections::HashMap;
-use std::error::Error;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::fs;
 
-// hashmap<name, (key, api url)>
+use super::{registry, ChainConfig, EtherscanV2, ParseError, RateLimiter};
+
+// current layout of keys.json, bump on every incompatible change
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+// db<name, chain config>, rate_limits<name, limiter>, 
+// optional etherscan multichain key with its own limiter
 
 #[derive(Debug)]
 pub struct ApiDB {
-    pub db: HashMap<String, (String, String)>
+    pub db: HashMap<String, ChainConfig>,
+    pub rate_limits: HashMap<String, RateLimiter>,
+    pub etherscan_v2: Option<EtherscanV2>,
+    pub etherscan_v2_limiter: Option<RateLimiter>
+}
+
+// keys.json layout, chains are read as raw json to merge them over built-in ones
+
+#[derive(Serialize, Deserialize)]
+struct StoredDB<T> {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etherscan_v2: Option<EtherscanV2>,
+    chains: Vec<T>
+}
+
+// entry of tuple-style keys.json before schema versioning, requests per second is optional
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyApi {
+    Limited(String, String, f64),
+    Plain(String, String)
 }
 
 impl ApiDB {
@@ -16,78 +46,304 @@ impl ApiDB {
 
     pub fn new() -> Self {
         Self { 
-            db: HashMap::new() 
+            db: HashMap::new(),
+            rate_limits: HashMap::new(),
+            etherscan_v2: None,
+            etherscan_v2_limiter: None
         }
     }
 
-    // read from db
+    // read from db, tuple-style files are migrated and written back
 
-    pub async fn read(&mut self) -> Result<(), Box<dyn Error>> {
+    pub async fn read(&mut self) -> Result<(), ParseError> {
         let json_str = fs::read_to_string("./keys.json").await?;
-        let apis: HashMap<String, (String, String)> = serde_json::from_str(&json_str)?;
-        self.db = apis;
+        if self.load(&json_str)? {
+            self.write().await?;
+        }
         Ok(())
     }
 
-    // write to db
+    // fill db with built-in chains and keys.json content over them, 
+    // returns true if file had to be migrated
+
+    fn load(&mut self, json_str: &str) -> Result<bool, ParseError> {
+        let json: Value = serde_json::from_str(json_str)?;
+        let (user_chains, etherscan_v2, migrated) = match json.get("version") {
+            Some(_) => {
+                let stored: StoredDB<Value> = serde_json::from_value(json)?;
+                if stored.version > SCHEMA_VERSION {
+                    return Err(ParseError::Json(format!(
+                        "keys.json schema version {} is newer than supported {}", 
+                        stored.version, 
+                        SCHEMA_VERSION
+                    )))
+                }
+                (stored.chains, stored.etherscan_v2, false)
+            }
+            None => (ApiDB::migrate_legacy(json)?, None, true)
+        };
+        if let Some(etherscan_v2) = &etherscan_v2 {
+            ApiDB::check_rate_limit("etherscan_v2", etherscan_v2.rate_limit)?;
+        }
+        self.set_etherscan_v2(etherscan_v2);
+        self.db = registry::known_chains().into_iter()
+            .map(|chain| (chain.name.clone(), chain))
+            .collect();
+        for user_chain in user_chains {
+            let chain = self.merge_chain(user_chain)?;
+            ApiDB::check_rate_limit(&chain.name, chain.rate_limit)?;
+            self.db.insert(chain.name.clone(), chain);
+        }
+        self.rate_limits = self.db.values()
+            .map(|chain| (chain.name.clone(), RateLimiter::new(chain.rate_limit)))
+            .collect();
+        Ok(migrated)
+    }
+
+    // limiter waits 1 / rps between requests, so budget has to be a positive number,
+    // infinity would also be written to keys.json as null
+
+    fn is_valid_rate_limit(rps: f64) -> bool {
+        rps.is_finite() && rps > 0.0
+    }
+
+    fn check_rate_limit(name: &str, rps: f64) -> Result<(), ParseError> {
+        match ApiDB::is_valid_rate_limit(rps) {
+            true => Ok(()),
+            false => Err(ParseError::Json(format!("rate_limit of \"{}\" in keys.json must be a positive number, got {}", name, rps)))
+        }
+    }
+
+    // fields set in keys.json override fields of built-in chain with the same name
+
+    fn merge_chain(&self, user_chain: Value) -> Result<ChainConfig, ParseError> {
+        let name = user_chain["name"].as_str()
+            .ok_or_else(|| ParseError::Json("chain without name in keys.json".to_owned()))?;
+        let merged = match (self.db.get(name), user_chain.as_object()) {
+            (Some(known), Some(fields)) => {
+                let mut merged = serde_json::to_value(known)?;
+                for (field, value) in fields {
+                    merged[field] = value.to_owned();
+                }
+                merged
+            }
+            _ => user_chain
+        };
+        Ok(serde_json::from_value(merged)?)
+    }
+
+    // hashmap<name, (key, api url[, rps])> to chain fields
+
+    fn migrate_legacy(json: Value) -> Result<Vec<Value>, ParseError> {
+        let apis: HashMap<String, LegacyApi> = serde_json::from_value(json)?;
+        let chains = apis.into_iter()
+            .map(|(name, api)| {
+                let (key, url, rps) = match api {
+                    LegacyApi::Limited(key, url, rps) => (key, url, rps),
+                    LegacyApi::Plain(key, url) => (key, url, ChainConfig::DEFAULT_RATE_LIMIT)
+                };
+                serde_json::json!({
+                    "name": name,
+                    "api_url": url,
+                    "api_keys": [key],
+                    "rate_limit": rps
+                })
+            })
+            .collect();
+        Ok(chains)
+    }
 
-    async fn write(&self) -> Result<(), Box<dyn Error>> {
-        let json = serde_json::to_string_pretty(&self.db)?;
+    // write to db, built-in chains are written only if user changed them
+
+    async fn write(&self) -> Result<(), ParseError> {
+        let mut chains = self.db.values()
+            .filter(|chain| registry::known_chain(&chain.name).as_ref() != Some(*chain))
+            .cloned()
+            .collect::<Vec<_>>();
+        chains.sort_by(|a, b| a.name.cmp(&b.name));
+        let stored = StoredDB {
+            version: SCHEMA_VERSION,
+            etherscan_v2: self.etherscan_v2.clone(),
+            chains
+        };
+        let json = serde_json::to_string_pretty(&stored)?;
         fs::write("./keys.json", json).await?;
         Ok(())
     }
 
-    // change api key
+    // etherscan multichain key and chain id if chain can be fetched through it
 
-    pub async fn change_api_key(&mut self, name: &str, new_key: &str) -> Result<(), Box<dyn Error>> {
-        match self.db.get_mut(name) {
-            Some(val) => { 
-                val.0 = new_key.to_owned(); // val.0 is old key
-                self.write().await?;
-            },
-            None => {
-                eprintln!("No such name \"{}\" in database!", name);
+    pub fn etherscan_v2_for(&self, chain: &ChainConfig) -> Option<(&EtherscanV2, u64)> {
+        match (&self.etherscan_v2, chain.chain_id) {
+            (Some(etherscan_v2), Some(chain_id)) if chain.etherscan_v2 => Some((etherscan_v2, chain_id)),
+            _ => None
+        }
+    }
+
+    fn set_etherscan_v2(&mut self, etherscan_v2: Option<EtherscanV2>) {
+        self.etherscan_v2_limiter = etherscan_v2.as_ref()
+            .map(|etherscan_v2| RateLimiter::new(etherscan_v2.rate_limit));
+        self.etherscan_v2 = etherscan_v2;
+    }
+
+    // change etherscan multichain key, empty key removes it
+
+    pub async fn change_v2_key(&mut self, key: &str) -> Result<(), ParseError> {
+        let etherscan_v2 = match key.is_empty() {
+            true => None,
+            false => Some(EtherscanV2 {
+                api_key: key.to_owned(),
+                rate_limit: self.etherscan_v2.as_ref()
+                    .map_or(ChainConfig::DEFAULT_RATE_LIMIT, |etherscan_v2| etherscan_v2.rate_limit)
+            })
+        };
+        self.set_etherscan_v2(etherscan_v2);
+        self.write().await?;
+        Ok(())
+    }
+
+    // get chain config or error if name is unknown
+
+    fn get_chain_mut(&mut self, name: &str) -> Result<&mut ChainConfig, ParseError> {
+        self.db.get_mut(name).ok_or_else(|| ParseError::UnknownChain(name.to_owned()))
+    }
+
+    // find enabled chain by contract url host, the longest matching pattern wins
+
+    pub fn find_chain(&self, url: &str) -> Result<&ChainConfig, ParseError> {
+        let host = reqwest::Url::parse(url.trim()).ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .ok_or_else(|| ParseError::UnknownChain(url.to_owned()))?;
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+
+        let matches = self.db.values()
+            .filter(|chain| chain.enabled)
+            .filter_map(|chain| chain.match_host(host).map(|len| (len, chain)))
+            .collect::<Vec<_>>();
+        let best = matches.iter().map(|(len, _)| *len).max()
+            .ok_or_else(|| ParseError::UnknownChain(url.to_owned()))?;
+        let mut best_chains = matches.into_iter()
+            .filter(|(len, _)| *len == best)
+            .map(|(_, chain)| chain)
+            .collect::<Vec<_>>();
+
+        match best_chains.len() {
+            1 => Ok(best_chains.remove(0)),
+            _ => {
+                let mut names = best_chains.iter().map(|chain| chain.name.as_str()).collect::<Vec<_>>();
+                names.sort();
+                Err(ParseError::AmbiguousChain(format!("{} matches {}", url, names.join(", "))))
             }
         }
+    }
+
+    // change api key
+
+    pub async fn change_api_key(&mut self, name: &str, new_key: &str) -> Result<(), ParseError> {
+        self.get_chain_mut(name)?.api_keys = vec![new_key.to_owned()];
+        self.write().await?;
         Ok(())
     }
 
     // change api url
 
-    pub async fn change_api_url(&mut self, name: &str, new_url: &str) -> Result<(), Box<dyn Error>> {
-        match self.db.get_mut(name) {
-            Some(val) => { 
-                val.1 = new_url.to_owned(); // val.1 is old api url
-                self.write().await?;
-            },
-            None => {
-                eprintln!("No such name \"{}\" in database!", name);
-            }
+    pub async fn change_api_url(&mut self, name: &str, new_url: &str) -> Result<(), ParseError> {
+        self.get_chain_mut(name)?.api_url = new_url.to_owned();
+        self.write().await?;
+        Ok(())
+    }
+
+    // change requests per second budget
+
+    pub async fn change_api_rps(&mut self, name: &str, rps: f64) -> Result<(), ParseError> {
+        if !ApiDB::is_valid_rate_limit(rps) {
+            return Err(ParseError::InvalidArgs(format!("rps must be a positive number, got {}", rps)))
         }
+        self.get_chain_mut(name)?.rate_limit = rps;
+        self.rate_limits.insert(name.to_owned(), RateLimiter::new(rps));
+        self.write().await?;
+        Ok(())
+    }
+
+    // change json-rpc endpoint used to read proxy slots, empty url removes it
+
+    pub async fn change_api_rpc(&mut self, name: &str, rpc_url: &str) -> Result<(), ParseError> {
+        self.get_chain_mut(name)?.rpc_url = Some(rpc_url.to_owned()).filter(|url| !url.is_empty());
+        self.write().await?;
         Ok(())
     }
 
-    // add new chain to db
+    // enable or disable chain without removing its keys
 
-    pub async fn add_new_api(&mut self, name: &str, key: &str, api: &str) -> Result<(), Box<dyn Error>> {
-        self.db.insert(name.to_owned(), (key.to_owned(), api.to_owned()));
+    pub async fn change_api_enabled(&mut self, name: &str, enabled: bool) -> Result<(), ParseError> {
+        self.get_chain_mut(name)?.enabled = enabled;
         self.write().await?;
         Ok(())
     }
 
-    // remove chain from db
+    // add new chain to db, known chains need only a key
 
-    pub async fn remove_api(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
-        self.db.remove(name);
+    pub async fn add_new_api(
+        &mut self, 
+        name: &str, 
+        key: &str, 
+        api: Option<&str>, 
+        hosts: &[String]
+    ) -> Result<(), ParseError> {
+        let mut chain = match (registry::known_chain(name), api) {
+            (Some(mut known), api) => {
+                known.api_keys = vec![key.to_owned()];
+                if let Some(api) = api {
+                    known.api_url = api.to_owned();
+                }
+                known
+            }
+            (None, Some(api)) => ChainConfig::new(name, key, api),
+            (None, None) => return Err(ParseError::InvalidArgs(format!(
+                "\"{}\" is not a known chain, api url is required", 
+                name
+            )))
+        };
+        if !hosts.is_empty() {
+            chain.hosts = hosts.to_vec();
+        }
+        self.rate_limits.insert(name.to_owned(), RateLimiter::new(chain.rate_limit));
+        self.db.insert(name.to_owned(), chain);
         self.write().await?;
         Ok(())
     }
+
+    // remove chain from db, built-in chains fall back to their defaults
+
+    pub async fn remove_api(&mut self, name: &str) -> Result<(), ParseError> {
+        match registry::known_chain(name) {
+            Some(known) => {
+                self.rate_limits.insert(name.to_owned(), RateLimiter::new(known.rate_limit));
+                self.db.insert(name.to_owned(), known);
+            }
+            None => {
+                self.db.remove(name);
+                self.rate_limits.remove(name);
+            }
+        }
+        self.write().await?;
+        Ok(())
+    }
+
+    // all chains sorted by name
+
+    pub fn chains(&self) -> Vec<&ChainConfig> {
+        let mut chains = self.db.values().collect::<Vec<_>>();
+        chains.sort_by(|a, b| a.name.cmp(&b.name));
+        chains
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::error::Error;
 
     #[tokio::test]
     async fn database_functions_test() -> Result<(), Box<dyn Error>> {
@@ -95,19 +351,39 @@ mod tests {
         db.read().await?;
 
         // add new api test
-        db.add_new_api("test", "test_key", "test_api").await?;
+        db.add_new_api("test", "test_key", Some("test_api"), &[]).await?;
         db.read().await?;
-        assert_eq!(db.db.get("test"), Some(&("test_key".to_owned(), "test_api".to_owned())));
+        assert_eq!(db.db.get("test"), Some(&ChainConfig::new("test", "test_key", "test_api")));
 
         // change api key test
         db.change_api_key("test", "new_key").await?;
         db.read().await?;
-        assert_eq!(db.db.get("test").unwrap().0, "new_key".to_owned());
+        assert_eq!(db.db.get("test").unwrap().api_keys, vec!["new_key".to_owned()]);
         
         // change api url test
         db.change_api_url("test", "new_api_url").await?;
         db.read().await?;
-        assert_eq!(db.db.get("test").unwrap().1, "new_api_url".to_owned());
+        assert_eq!(db.db.get("test").unwrap().api_url, "new_api_url".to_owned());
+
+        // change api rps test
+        db.change_api_rps("test", 2.0).await?;
+        db.read().await?;
+        assert_eq!(db.db.get("test").unwrap().rate_limit, 2.0);
+        for rps in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            assert!(matches!(db.change_api_rps("test", rps).await, Err(ParseError::InvalidArgs(_))));
+        }
+        db.read().await?;
+        assert_eq!(db.db.get("test").unwrap().rate_limit, 2.0);
+
+        // change api rpc test
+        db.change_api_rpc("test", "https://rpc.test").await?;
+        db.read().await?;
+        assert_eq!(db.db.get("test").unwrap().rpc_url.as_deref(), Some("https://rpc.test"));
+
+        // disable api test
+        db.change_api_enabled("test", false).await?;
+        db.read().await?;
+        assert!(!db.db.get("test").unwrap().enabled);
 
         // remove api test
         db.remove_api("test").await?;
@@ -116,4 +392,89 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn legacy_migration_test() -> Result<(), Box<dyn Error>> {
+        let mut db = ApiDB::new();
+        let legacy = r#"{
+            "etherscan": ["key", "https://api.etherscan.io"],
+            "bscscan": ["bsc_key", "https://api.bscscan.com", 2.0]
+        }"#;
+        assert!(db.load(legacy)?);
+
+        let etherscan = db.db.get("etherscan").unwrap();
+        assert_eq!(etherscan.api_keys, vec!["key".to_owned()]);
+        assert_eq!(etherscan.api_url, "https://api.etherscan.io");
+        assert_eq!(etherscan.rate_limit, ChainConfig::DEFAULT_RATE_LIMIT);
+        assert_eq!(db.db.get("bscscan").unwrap().rate_limit, 2.0);
+
+        // legacy entries keep built-in hosts and chain id
+        assert_eq!(etherscan.hosts, vec!["etherscan.io".to_owned()]);
+        assert_eq!(etherscan.chain_id, Some(1));
+
+        // versioned file is loaded as is
+        let current = r#"{"version": 1, "chains": [{"name": "custom", "api_url": "https://api.custom.io"}]}"#;
+        assert!(!db.load(current)?);
+        assert!(db.db.get("custom").unwrap().enabled);
+        assert!(db.db.get("bscscan").unwrap().api_keys.is_empty());
+
+        // etherscan multichain key applies to chains with chain id and v2 support
+        let v2 = r#"{"version": 1, "etherscan_v2": {"api_key": "v2_key"}, "chains": []}"#;
+        assert!(!db.load(v2)?);
+        let (etherscan_v2, chain_id) = db.etherscan_v2_for(&db.db["arbiscan"]).unwrap();
+        assert_eq!((etherscan_v2.api_key.as_str(), chain_id), ("v2_key", 42161));
+        assert!(db.etherscan_v2_for(&db.db["ftmscan"]).is_none());
+
+        // newer schema is rejected
+        let newer = r#"{"version": 99, "chains": []}"#;
+        assert!(db.load(newer).is_err());
+
+        // non-positive rate limits are rejected wherever they are set
+        for invalid in [
+            r#"{"version": 1, "chains": [{"name": "etherscan", "rate_limit": 0}]}"#,
+            r#"{"version": 1, "chains": [{"name": "custom", "api_url": "https://api.custom.io", "rate_limit": -1.5}]}"#,
+            r#"{"version": 1, "etherscan_v2": {"api_key": "v2_key", "rate_limit": 0.0}, "chains": []}"#,
+            r#"{"bscscan": ["bsc_key", "https://api.bscscan.com", -2.0]}"#
+        ] {
+            assert!(matches!(db.load(invalid), Err(ParseError::Json(_))));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn find_chain_test() -> Result<(), Box<dyn Error>> {
+        let mut db = ApiDB::new();
+        db.load(r#"{"version": 1, "chains": [
+            {"name": "example", "api_url": "https://api.examplescan.io"},
+            {"name": "layer2", "api_url": "https://api-layer2.examplescan.io"},
+            {"name": "wild", "hosts": ["wildscan.com", "*.wildscan.com"], "api_url": "https://api.wildscan.com"},
+            {"name": "testnet", "hosts": ["testnet.wildscan.com"], "api_url": "https://api-testnet.wildscan.com"},
+            {"name": "disabled", "hosts": ["offscan.io"], "api_url": "https://api.offscan.io", "enabled": false},
+            {"name": "copy", "hosts": ["twinscan.io"], "api_url": "https://api.twinscan.io"},
+            {"name": "paste", "hosts": ["twinscan.io"], "api_url": "https://api.twinscan.io"}
+        ]}"#)?;
+
+        // built-in hosts
+        assert_eq!(db.find_chain("https://etherscan.io/address/0x0")?.name, "etherscan");
+        assert_eq!(db.find_chain("https://www.etherscan.io/address/0x0")?.name, "etherscan");
+        assert_eq!(db.find_chain("https://optimistic.etherscan.io/address/0x0")?.name, "optimistic");
+
+        // hosts derived from api url
+        assert_eq!(db.find_chain("https://examplescan.io/address/0x0")?.name, "example");
+        assert_eq!(db.find_chain("https://layer2.examplescan.io/address/0x0")?.name, "layer2");
+
+        // wildcard and longest match
+        assert_eq!(db.find_chain("https://wildscan.com/address/0x0")?.name, "wild");
+        assert_eq!(db.find_chain("https://other.wildscan.com/address/0x0")?.name, "wild");
+        assert_eq!(db.find_chain("https://testnet.wildscan.com/address/0x0")?.name, "testnet");
+
+        // no match, disabled chain, not a url, ambiguous
+        assert!(matches!(db.find_chain("https://other.examplescan.io/address/0x0"), Err(ParseError::UnknownChain(_))));
+        assert!(matches!(db.find_chain("https://offscan.io/address/0x0"), Err(ParseError::UnknownChain(_))));
+        assert!(matches!(db.find_chain("etherscan"), Err(ParseError::UnknownChain(_))));
+        assert!(matches!(db.find_chain("https://twinscan.io/address/0x0"), Err(ParseError::AmbiguousChain(_))));
+
+        Ok(())
+    }
+}
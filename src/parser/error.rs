@@ -0,0 +1,126 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+// every failure the parser can run into, grouped so callers can tell them apart
+
+#[derive(Debug)]
+pub enum ParseError {
+    Network(reqwest::Error),
+    ExplorerStatus(String),
+    Unverified(String),
+    AddressNotFound(String),
+    SelectorMiss(String),
+    Json(String),
+    Fs(std::io::Error),
+    UnknownChain(String),
+    InvalidArgs(String),
+    RateLimited(String),
+    InvalidApiKey(String),
+    AmbiguousChain(String),
+    UnsafePath(String)
+}
+
+impl ParseError {
+
+    // process exit code for each error category
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ParseError::InvalidArgs(_) => 2,
+            ParseError::Network(_) => 3,
+            ParseError::ExplorerStatus(_) => 4,
+            ParseError::Unverified(_) => 5,
+            ParseError::AddressNotFound(_) => 6,
+            ParseError::SelectorMiss(_) => 7,
+            ParseError::Json(_) => 8,
+            ParseError::Fs(_) => 9,
+            ParseError::UnknownChain(_) => 10,
+            ParseError::RateLimited(_) => 11,
+            ParseError::InvalidApiKey(_) => 12,
+            ParseError::AmbiguousChain(_) => 13,
+            ParseError::UnsafePath(_) => 14
+        }
+    }
+
+    // error of the category most contracts failed with, so batch runs exit with a meaningful code,
+    // ties go to the lower exit code
+
+    pub fn most_common(errors: Vec<ParseError>) -> Option<ParseError> {
+        let mut counts: HashMap<i32, usize> = HashMap::new();
+        for why in &errors {
+            *counts.entry(why.exit_code()).or_default() += 1;
+        }
+        errors.into_iter().max_by_key(|why| (counts[&why.exit_code()], Reverse(why.exit_code())))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Network(why) => write!(f, "network error: {}", why),
+            ParseError::ExplorerStatus(message) => write!(f, "explorer api error: {}", message),
+            ParseError::Unverified(address) => write!(f, "contract source code not verified: {}", address),
+            ParseError::AddressNotFound(url) => write!(f, "couldn't find contract address in {}", url),
+            ParseError::SelectorMiss(page) => write!(f, "wrong {} or selector", page),
+            ParseError::Json(why) => write!(f, "unexpected json: {}", why),
+            ParseError::Fs(why) => write!(f, "filesystem error: {}", why),
+            ParseError::UnknownChain(name) => write!(f, "no enabled chain in api database matches \"{}\"", name),
+            ParseError::InvalidArgs(why) => write!(f, "invalid arguments: {}", why),
+            ParseError::RateLimited(message) => write!(f, "explorer rate limit reached: {}", message),
+            ParseError::InvalidApiKey(message) => write!(f, "explorer rejected api key: {}", message),
+            ParseError::AmbiguousChain(message) => write!(f, "more than one chain in api database: {}", message),
+            ParseError::UnsafePath(path) => write!(f, "source path escapes output folder: \"{}\"", path)
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Network(why) => Some(why),
+            ParseError::Fs(why) => Some(why),
+            _ => None
+        }
+    }
+}
+
+impl From<reqwest::Error> for ParseError {
+    fn from(why: reqwest::Error) -> Self {
+        ParseError::Network(why)
+    }
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(why: serde_json::Error) -> Self {
+        ParseError::Json(why.to_string())
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(why: std::io::Error) -> Self {
+        ParseError::Fs(why)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_common_error_test() {
+        assert!(ParseError::most_common(Vec::new()).is_none());
+        let errors = vec![
+            ParseError::Unverified("0x1".to_owned()),
+            ParseError::RateLimited("max rate".to_owned()),
+            ParseError::Unverified("0x2".to_owned()),
+            ParseError::RateLimited("max rate".to_owned()),
+            ParseError::RateLimited("max rate".to_owned())
+        ];
+        assert_eq!(ParseError::most_common(errors).map(|why| why.exit_code()), Some(11));
+
+        let tie = vec![ParseError::RateLimited("max rate".to_owned()), ParseError::Unverified("0x1".to_owned())];
+        assert_eq!(ParseError::most_common(tie).map(|why| why.exit_code()), Some(5));
+    }
+}
@@ -0,0 +1,91 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+
+use super::ParseError;
+
+// include/exclude globs over source paths, "*" stays inside one folder and "**" crosses them
+
+#[derive(Debug, Clone)]
+pub struct SourceFilter {
+    include: GlobSet,
+    exclude: GlobSet
+}
+
+// --filters file, e.g. { "include": ["contracts/**"], "exclude": ["lib/forge-std/**"] }
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>
+}
+
+impl SourceFilter {
+
+    // what parser always skipped: openzeppelin libraries and nested import.sol files
+
+    pub const DEFAULT_EXCLUDE: &'static [&'static str] = &["**/*@openzeppelin*/**", "*/**/import.sol"];
+
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, ParseError> {
+        Ok(Self {
+            include: SourceFilter::build(include)?,
+            exclude: SourceFilter::build(exclude)?
+        })
+    }
+
+    fn build(patterns: &[String]) -> Result<GlobSet, ParseError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern.trim())
+                .literal_separator(true)
+                .build()
+                .map_err(|why| ParseError::InvalidArgs(format!("glob \"{}\": {}", pattern, why)))?;
+            builder.add(glob);
+        }
+        builder.build().map_err(|why| ParseError::InvalidArgs(why.to_string()))
+    }
+
+    // path is saved when it matches some include (or there are none) and no exclude
+
+    pub fn is_match(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.is_match(path)) && !self.exclude.is_match(path)
+    }
+}
+
+impl Default for SourceFilter {
+    fn default() -> Self {
+        let exclude: Vec<String> = SourceFilter::DEFAULT_EXCLUDE.iter().map(|glob| glob.to_string()).collect();
+        Self::new(&[], &exclude).expect("valid default globs")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_filter_test() -> Result<(), ParseError> {
+        let filter = SourceFilter::default();
+        assert!(filter.is_match("contracts/Token.sol"));
+        assert!(filter.is_match("import.sol"));
+        assert!(!filter.is_match("@openzeppelin/contracts/token/ERC20/ERC20.sol"));
+        assert!(!filter.is_match("node_modules/@openzeppelin/contracts-upgradeable/proxy/utils/Initializable.sol"));
+        assert!(!filter.is_match("contracts/import.sol"));
+        assert!(!filter.is_match("contracts/deps/import.sol"));
+        assert!(SourceFilter::new(&[], &[])?.is_match("@openzeppelin/contracts/access/Ownable.sol"));
+
+        let filter = SourceFilter::new(
+            &["contracts/**".to_owned()],
+            &["lib/forge-std/**".to_owned(), "node_modules/**".to_owned(), "**/test/*.sol".to_owned()]
+        )?;
+        assert!(filter.is_match("contracts/Vault.sol"));
+        assert!(filter.is_match("contracts/strategies/Curve.sol"));
+        assert!(!filter.is_match("contracts/test/Mock.sol"));
+        assert!(!filter.is_match("lib/forge-std/src/Test.sol"));
+        assert!(!filter.is_match("src/Vault.sol"));
+
+        assert!(matches!(SourceFilter::new(&["a/[".to_owned()], &[]), Err(ParseError::InvalidArgs(_))));
+        Ok(())
+    }
+}
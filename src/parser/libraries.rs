@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use tokio::fs;
+
+use super::{get_content_hash, ParseError};
+
+// fingerprints of known releases, built by index_library (or scripts/index_releases.sh)
+
+pub const LIBRARY_DB_PATH: &str = "./library_db.json";
+
+// upstream sources copied by index_library as <library>/<version>/<path>, used for diffs
+
+pub const LIBRARY_CACHE_DIR: &str = "./library_cache";
+
+const LIBRARY_DB_VERSION: u32 = 1;
+
+// one release of a library, paths are relative to its package root
+// e.g. "token/ERC20/ERC20.sol" of openzeppelin-contracts 4.9.0
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryRelease {
+    pub name: String,
+    pub version: String,
+    pub files: BTreeMap<String, String>
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibraryDB {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub libraries: Vec<LibraryRelease>
+}
+
+// libraries.json entry of one contract
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryReport {
+    pub library: String,
+    pub version: Option<String>,
+    pub candidates: Vec<String>,
+    pub files: Vec<String>,
+    pub modified: Vec<String>
+}
+
+// unified diff of a modified library file against the closest cached release
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamDiff {
+    pub version: String,
+    pub upstream_path: String,
+    pub diff: String
+}
+
+// library found while fingerprinting, versions is none until some file matched exactly
+
+#[derive(Default)]
+struct Detected<'a> {
+    versions: Option<BTreeSet<&'a str>>,
+    files: Vec<String>,
+    modified: Vec<String>
+}
+
+// line endings and trailing whitespace don't change code, so they don't change the hash
+
+pub fn normalize_source(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().map(str::trim_end).collect();
+    let start = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|line| !line.is_empty()).map_or(start, |end| end + 1);
+    lines[start..end].join("\n")
+}
+
+pub fn get_source_hash(content: &str) -> String {
+    get_content_hash(&normalize_source(content))
+}
+
+// "v4.9.0" -> [4, 9, 0], used to pick the newest candidate
+
+fn version_key(version: &str) -> Vec<u64> {
+    version.trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+// vendored path ends with release path, "@openzeppelin/contracts/access/Ownable.sol" ~ "access/Ownable.sol"
+
+fn is_release_path(vendored: &str, release_path: &str) -> bool {
+    vendored.strip_suffix(release_path).is_some_and(|root| root.is_empty() || root.ends_with('/'))
+}
+
+// folder before release path names the library, "@openzeppelin/contracts/", "lib/solmate/src/",
+// "node_modules/@openzeppelin/contracts-upgradeable/", so project's own "contracts/interfaces/IERC20.sol"
+// is never taken for modified library code
+
+pub fn is_library_root(vendored: &str, library: &str, release_path: &str) -> bool {
+    let stem = library.split('-').next().unwrap_or(library).to_lowercase();
+    match vendored.strip_suffix(release_path) {
+        Some(root) if root.ends_with('/') => root.to_lowercase().split('/').any(|folder| folder.contains(&stem)),
+        _ => false
+    }
+}
+
+impl LibraryDB {
+
+    // local database, empty if nothing was indexed yet
+
+    pub async fn load() -> Result<Self, ParseError> {
+        match fs::read_to_string(LIBRARY_DB_PATH).await {
+            Ok(json_str) => Ok(serde_json::from_str(&json_str)?),
+            Err(_) => Ok(LibraryDB::default())
+        }
+    }
+
+    // files of a release that is already known are added to it, so indexing a subfolder never drops hashes
+
+    fn merge(&mut self, release: LibraryRelease) {
+        match self.libraries.iter_mut().find(|known| known.name == release.name && known.version == release.version) {
+            Some(known) => known.files.extend(release.files),
+            None => self.libraries.push(release)
+        }
+        self.libraries.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| version_key(&a.version).cmp(&version_key(&b.version))));
+    }
+
+    // hash every .sol/.vy file of a local release checkout into ./library_db.json
+    // and keep a copy of it in the cache for diffs
+
+    pub async fn index_release(name: &str, version: &str, dir: &Path, cache: &Path) -> Result<LibraryRelease, ParseError> {
+        let version = version.trim_start_matches('v');
+        let release_dir = cache.join(name).join(version);
+        let mut files = BTreeMap::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(current) = dirs.pop() {
+            let mut entries = fs::read_dir(&current).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path);
+                    continue
+                }
+                if !matches!(path.extension().and_then(|ext| ext.to_str()), Some("sol" | "vy")) {
+                    continue
+                }
+                let relative = path.strip_prefix(dir).unwrap_or(&path)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let content = fs::read_to_string(&path).await?;
+                let cached = release_dir.join(&relative);
+                if let Some(parent) = cached.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                fs::write(&cached, &content).await?;
+                files.insert(relative, get_source_hash(&content));
+            }
+        }
+        let release = LibraryRelease {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            files
+        };
+
+        let mut local = match fs::read_to_string(LIBRARY_DB_PATH).await {
+            Ok(json_str) => serde_json::from_str(&json_str)?,
+            Err(_) => LibraryDB::default()
+        };
+        local.version = LIBRARY_DB_VERSION;
+        local.merge(release.clone());
+        fs::write(LIBRARY_DB_PATH, serde_json::to_string_pretty(&local)?).await?;
+        Ok(release)
+    }
+
+    // match saved sources against known releases: exact hashes name library and version,
+    // files at a release path under a library root with unknown hash are reported as modified
+
+    pub fn fingerprint(&self, sources: &[(&str, &str)]) -> Vec<LibraryReport> {
+        let mut reports: BTreeMap<&str, Detected> = BTreeMap::new();
+        let mut unmatched = Vec::new();
+
+        // hash -> (library, version, release path) and release path -> libraries, built once per contract
+
+        let mut by_hash: HashMap<&str, Vec<(&str, &str, &str)>> = HashMap::new();
+        let mut by_path: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+        for release in &self.libraries {
+            for (release_path, hash) in &release.files {
+                by_hash.entry(hash.as_str()).or_default().push((&release.name, &release.version, release_path));
+                by_path.entry(release_path.as_str()).or_default().insert(&release.name);
+            }
+        }
+
+        for (path, content) in sources {
+            let hash = get_source_hash(content);
+
+            // releases holding exactly this file, grouped by library
+
+            let mut found: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+            for (library, version, release_path) in by_hash.get(hash.as_str()).into_iter().flatten() {
+                if is_release_path(path, release_path) {
+                    found.entry(library).or_default().insert(version);
+                }
+            }
+            if found.is_empty() {
+                unmatched.push(*path);
+            }
+            for (library, versions) in found {
+                let report = reports.entry(library).or_default();
+                report.versions = Some(match report.versions.take() {
+                    Some(candidates) if candidates.intersection(&versions).next().is_some() => {
+                        candidates.intersection(&versions).copied().collect()
+                    }
+                    Some(candidates) => candidates,
+                    None => versions
+                });
+                report.files.push(path.to_string());
+            }
+        }
+
+        // unknown content at a release path under a library root, detected libraries are preferred
+
+        for path in unmatched {
+            let library = path.match_indices('/')
+                .map(|(index, _)| &path[index + 1..])
+                .filter_map(|release_path| by_path.get(release_path).map(|libraries| (release_path, libraries)))
+                .flat_map(|(release_path, libraries)| {
+                    libraries.iter().copied().filter(move |library| is_library_root(path, library, release_path))
+                })
+                .min_by_key(|library| !reports.contains_key(library));
+            if let Some(library) = library {
+                reports.entry(library).or_default().modified.push(path.to_owned());
+            }
+        }
+
+        reports.into_iter()
+            .map(|(library, detected)| {
+                let mut candidates: Vec<String> = detected.versions.unwrap_or_default().into_iter().map(str::to_owned).collect();
+                candidates.sort_by_key(|version| version_key(version));
+                LibraryReport {
+                    library: library.to_owned(),
+                    version: candidates.last().cloned(),
+                    candidates,
+                    files: detected.files,
+                    modified: detected.modified
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.libraries.is_empty()
+    }
+
+    // diff vendored file against every cached release of library holding its path,
+    // the most similar one is the closest upstream version
+
+    pub async fn diff_upstream(
+        &self, 
+        cache: &Path, 
+        library: &str, 
+        vendored_path: &str, 
+        content: &str
+    ) -> Result<Option<UpstreamDiff>, ParseError> {
+        let vendored = normalize_source(content) + "\n";
+        let mut closest: Option<(f32, UpstreamDiff)> = None;
+        for release in self.libraries.iter().filter(|release| release.name == library) {
+            let upstream_path = match get_release_path(release, vendored_path) {
+                Some(upstream_path) => upstream_path,
+                None => continue
+            };
+            let upstream = match fs::read_to_string(get_release_dir(cache, release).join(upstream_path)).await {
+                Ok(upstream) => normalize_source(&upstream) + "\n",
+                Err(_) => continue
+            };
+            let diff = TextDiff::from_lines(&upstream, &vendored);
+            let ratio = diff.ratio();
+            if closest.as_ref().is_some_and(|(best, _)| *best >= ratio) {
+                continue
+            }
+            let unified = diff.unified_diff()
+                .context_radius(3)
+                .header(&format!("{}@{}/{}", release.name, release.version, upstream_path), vendored_path)
+                .to_string();
+            closest = Some((ratio, UpstreamDiff {
+                version: release.version.clone(),
+                upstream_path: upstream_path.to_owned(),
+                diff: unified
+            }));
+        }
+        Ok(closest.map(|(_, diff)| diff))
+    }
+}
+
+// relative path of vendored file inside release, longest match under a library root wins
+
+fn get_release_path<'a>(release: &'a LibraryRelease, vendored: &str) -> Option<&'a str> {
+    release.files.keys()
+        .filter(|release_path| is_library_root(vendored, &release.name, release_path))
+        .max_by_key(|release_path| release_path.len())
+        .map(String::as_str)
+}
+
+// folder of indexed release inside cache
+
+pub fn get_release_dir(cache: &Path, release: &LibraryRelease) -> PathBuf {
+    cache.join(&release.name).join(&release.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(name: &str, version: &str, files: &[(&str, &str)]) -> LibraryRelease {
+        LibraryRelease {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            files: files.iter().map(|(path, content)| (path.to_string(), get_source_hash(content))).collect()
+        }
+    }
+
+    #[test]
+    fn fingerprint_test() {
+        assert_eq!(normalize_source("\r\n\ncontract A {   \r\n}\r\n\n"), "contract A {\n}");
+
+        let db = LibraryDB {
+            version: LIBRARY_DB_VERSION,
+            libraries: vec![
+                release("openzeppelin-contracts", "4.8.0", &[("access/Ownable.sol", "ownable v1"), ("utils/Context.sol", "context")]),
+                release("openzeppelin-contracts", "4.9.0", &[
+                    ("access/Ownable.sol", "ownable v2"), 
+                    ("utils/Context.sol", "context"), 
+                    ("interfaces/IERC20.sol", "oz ierc20")
+                ]),
+                release("solmate", "6.2.0", &[("tokens/ERC20.sol", "solmate erc20")])
+            ]
+        };
+        let sources = [
+            ("contracts/Vault.sol", "contract Vault {}"),
+            // project's own interface at a generic library path is not library code
+            ("contracts/interfaces/IERC20.sol", "project ierc20"),
+            ("lib/openzeppelin-contracts/contracts/interfaces/IERC20.sol", "oz ierc20 with a backdoor"),
+            ("@openzeppelin/contracts/utils/Context.sol", "context\r\n"),
+            ("@openzeppelin/contracts/access/Ownable.sol", "ownable v2"),
+            ("lib/solmate/src/tokens/ERC20.sol", "solmate erc20 with a backdoor")
+        ];
+        let mut merged = db.clone();
+        merged.merge(release("openzeppelin-contracts", "4.9.0", &[("access/Ownable.sol", "ownable v2"), ("proxy/Proxy.sol", "proxy")]));
+        merged.merge(release("solady", "0.0.200", &[("utils/LibString.sol", "lib string")]));
+        assert_eq!(merged.libraries.len(), 4);
+        assert_eq!(merged.libraries[1].files.len(), 4);
+        assert_eq!(merged.libraries[3].name, "solmate");
+
+        let reports = db.fingerprint(&sources);
+        assert_eq!(reports, vec![
+            LibraryReport {
+                library: "openzeppelin-contracts".to_owned(),
+                version: Some("4.9.0".to_owned()),
+                candidates: vec!["4.9.0".to_owned()],
+                files: vec![
+                    "@openzeppelin/contracts/utils/Context.sol".to_owned(),
+                    "@openzeppelin/contracts/access/Ownable.sol".to_owned()
+                ],
+                modified: vec!["lib/openzeppelin-contracts/contracts/interfaces/IERC20.sol".to_owned()]
+            },
+            LibraryReport {
+                library: "solmate".to_owned(),
+                version: None,
+                candidates: Vec::new(),
+                files: Vec::new(),
+                modified: vec!["lib/solmate/src/tokens/ERC20.sol".to_owned()]
+            }
+        ]);
+
+        assert!(is_library_root("node_modules/@openzeppelin/contracts-upgradeable/access/OwnableUpgradeable.sol", "openzeppelin-contracts-upgradeable", "access/OwnableUpgradeable.sol"));
+        assert!(is_library_root("lib/solady/src/utils/LibString.sol", "solady", "utils/LibString.sol"));
+        assert!(!is_library_root("src/utils/LibString.sol", "solady", "utils/LibString.sol"));
+        assert!(!is_library_root("utils/LibString.sol", "solady", "utils/LibString.sol"));
+    }
+
+    #[tokio::test]
+    async fn upstream_diff_test() -> Result<(), Box<dyn std::error::Error>> {
+        let root = std::env::temp_dir().join(format!("iscp-upstream-{}", std::process::id()));
+        let cache = root.join("cache");
+        let checkout = root.join("solmate");
+        let original = "contract ERC20 {\n    function transfer() public {\n        balance -= amount;\n    }\n}\n";
+        let older = "contract ERC20 {\n    function transfer() external {\n    }\n}\n";
+        let mut db = LibraryDB::default();
+        for (version, content) in [("6.1.0", older), ("6.2.0", original)] {
+            fs::create_dir_all(checkout.join(version).join("tokens")).await?;
+            fs::write(checkout.join(version).join("tokens/ERC20.sol"), content).await?;
+            let files = [("tokens/ERC20.sol".to_owned(), get_source_hash(content))].into_iter().collect();
+            db.libraries.push(LibraryRelease { name: "solmate".to_owned(), version: version.to_owned(), files });
+            fs::create_dir_all(get_release_dir(&cache, db.libraries.last().unwrap()).join("tokens")).await?;
+            fs::copy(checkout.join(version).join("tokens/ERC20.sol"), get_release_dir(&cache, db.libraries.last().unwrap()).join("tokens/ERC20.sol")).await?;
+        }
+
+        let modified = original.replace("balance -= amount;", "balance -= amount;\n        owner = msg.sender;");
+        let diff = db.diff_upstream(&cache, "solmate", "lib/solmate/src/tokens/ERC20.sol", &modified).await?.unwrap();
+        assert_eq!(diff.version, "6.2.0");
+        assert_eq!(diff.upstream_path, "tokens/ERC20.sol");
+        assert!(diff.diff.starts_with("--- solmate@6.2.0/tokens/ERC20.sol\n+++ lib/solmate/src/tokens/ERC20.sol\n"));
+        assert!(diff.diff.contains("\n+        owner = msg.sender;\n"));
+        assert_eq!(diff.diff.lines().filter(|line| line.starts_with('+') || line.starts_with('-')).count(), 3);
+
+        assert_eq!(db.diff_upstream(&cache, "solady", "lib/solmate/src/tokens/ERC20.sol", &modified).await?, None);
+        // project file at a release path is not diffed against the library
+        assert_eq!(db.diff_upstream(&cache, "solmate", "contracts/tokens/ERC20.sol", &modified).await?, None);
+        fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+}
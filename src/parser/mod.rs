@@ -4,13 +4,51 @@ pub use apidb::*;
 mod models;
 pub use models::*;
 
-use std::error::Error;
+mod error;
+pub use error::*;
+
+mod retry;
+pub use retry::*;
+
+mod ratelimit;
+pub use ratelimit::*;
+
+mod registry;
+
+mod providers;
+pub use providers::*;
+
+mod rpc;
+pub use rpc::*;
+
+mod project;
+pub use project::*;
+
+mod filter;
+pub use filter::*;
+
+mod store;
+pub use store::*;
+
+mod libraries;
+pub use libraries::*;
+
+mod runstate;
+pub use runstate::*;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
 use tokio::fs;
 use futures::{stream, StreamExt};
 use scraper::{Html, Selector};
 use serde_json::Value;
 use regex::Regex;
 
+// proxies nested deeper than this are not followed
+
+const MAX_PROXY_DEPTH: usize = 8;
+
 pub struct Parser;
 
 impl Parser {
@@ -20,76 +58,151 @@ impl Parser {
     pub async fn immunefi_traverse(
         url: &str, 
         api: &ApiDB, 
-        open_zeppelin: bool, 
         folder_name: &str, 
-        limit: usize
-    ) -> Result<(), Box<dyn Error>> {
+        limit: Option<usize>,
+        options: &ParseOptions
+    ) -> Result<(), ParseError> {
 
         let response = reqwest::get(url).await?.text().await?;
         let document = Html::parse_document(&response);
         let contract_name_selector = 
-            Selector::parse("section.mb-12:nth-child(3) > div:nth-child(2)")?;
+            Selector::parse("section.mb-12:nth-child(3) > div:nth-child(2)")
+                .map_err(|why| ParseError::SelectorMiss(why.to_string()))?;
         let elems = document
             .select(&contract_name_selector)
             .collect::<Vec<_>>();
         if elems.is_empty() {
-            return Err("wrong immunefi page or selector".into())
-        }
-        let urls = elems[0].text()
-                        .filter(|&url| {
-                            api.db.keys().any(|name| url.contains(name))
-                        }).collect::<Vec<_>>();
-        
-        // concurrent option in case better api plan is provided(for free api set limit to 2)
-
-        stream::iter(urls).for_each_concurrent(limit, |url| async move {
-            let mode = 
-                ParserMode::Immunefi(folder_name.to_owned());
-            Parser::parse_contract(url, api, &mode, open_zeppelin)
-                .await
-                .unwrap_or_else(|why|{
-                    eprintln!("error parsing contract {} \n {}", url, why)
-                })
-        }).await;
-        
-        Ok(())
-    }
+            return Err(ParseError::SelectorMiss("immunefi page".to_owned()))
+        }
 
-    // concat url parts
+        // run.json of previous run, contracts parsed before are skipped unless forced
 
-    fn get_api_url(url: &str, contract_address: &str, apis: &ApiDB) -> String {
-        let mut key = String::new();
-        let mut api = String::new();
-    
-        for (name, key_and_api) in &apis.db {
-            if url.contains(name) {
-                (key, api) = key_and_api.to_owned();
-                break
+        let folder = Path::new(folder_name);
+        let mut run_state = match options.force {
+            true => RunState::default(),
+            false => RunState::load(folder).await?
+        };
+        let mut done = 0;
+
+        // group urls by chain, every chain is paced by its own rate limiter
+
+        let mut chains: HashMap<&str, Vec<&str>> = HashMap::new();
+        for url in elems[0].text() {
+            match api.find_chain(url) {
+                Ok(_) if run_state.is_done(url) => done += 1,
+                Ok(chain) => {
+                    run_state.set(url, RunStatus::Pending, None, None);
+                    chains.entry(chain.name.as_str()).or_default().push(url)
+                }
+                Err(ParseError::AmbiguousChain(why)) => {
+                    eprintln!("skipping contract: {}", why);
+                    run_state.set(url, RunStatus::Skipped, None, Some(why));
+                }
+                Err(_) => {}
             }
         }
+        run_state.save(folder).await?;
+        if done > 0 {
+            println!("{} contracts were parsed by previous run, use --force to fetch them again", done);
+        }
+
+        // chains run in parallel, optional limit caps requests in flight per chain
 
-        // workaround because etherscan is a subsrting of both etherscan.io and optimistic.etherscan.io
-        if url.contains("optimistic") {
-            (key, api) = apis.db.get("optimistic").unwrap().to_owned();
+        let run_state = tokio::sync::Mutex::new(run_state);
+        let failures = tokio::sync::Mutex::new(Vec::new());
+        let chain_streams = chains.into_values().map(|chain_urls| {
+            let run_state = &run_state;
+            let failures = &failures;
+            stream::iter(chain_urls).for_each_concurrent(limit, move |url| async move {
+                let mode = 
+                    ParserMode::Immunefi(folder_name.to_owned());
+                let result = Parser::parse_contract(url, api, &mode, options).await;
+                let mut run_state = run_state.lock().await;
+                match result {
+                    Ok(source_hash) => run_state.set(url, RunStatus::Ok, Some(source_hash), None),
+                    Err(why) => {
+                        eprintln!("error parsing contract {} \n {}", url, why);
+                        run_state.set(url, RunStatus::Failed, None, Some(why.to_string()));
+                        failures.lock().await.push(why);
+                    }
+                }
+                run_state.save(folder).await
+                    .unwrap_or_else(|why| eprintln!("couldn't save {}: {}", RUN_STATE_FILE, why));
+            })
+        });
+        futures::future::join_all(chain_streams).await;
+
+        let run_state = run_state.into_inner();
+        println!(
+            "{} ok, {} failed, {} skipped, state saved to {}", 
+            run_state.count(RunStatus::Ok), 
+            run_state.count(RunStatus::Failed), 
+            run_state.count(RunStatus::Skipped), 
+            folder.join(RUN_STATE_FILE).display()
+        );
+
+        // failed contracts make the whole run fail, with the code most of them failed with
+
+        match ParseError::most_common(failures.into_inner()) {
+            Some(why) => Err(why),
+            None => Ok(())
         }
-    
-        let api_url = format!("{}/api?module=contract&action=getsourcecode&address={}&apikey={}", 
-            api, 
+    }
+
+    // concat url parts
+
+    fn get_api_url(chain: &ChainConfig, contract_address: &str, key: &str) -> String {
+        format!("{}/api?module=contract&action=getsourcecode&address={}&apikey={}", 
+            chain.api_url, 
             contract_address, 
             key
-        );
+        )
+    }
+
+    // concat url parts of etherscan multichain api
+
+    fn get_v2_api_url(etherscan_v2: &EtherscanV2, chain_id: u64, contract_address: &str) -> String {
+        format!("{}?chainid={}&module=contract&action=getsourcecode&address={}&apikey={}", 
+            EtherscanV2::API_URL, 
+            chain_id,
+            contract_address, 
+            etherscan_v2.api_key
+        )
+    }
+
+    // request urls with their limiters and provider names in order of preference: 
+    // etherscan multichain api if chain supports it, then explorer of chain with each of its keys,
+    // keyless explorers get empty key
 
-        api_url
+    fn get_api_requests<'a>(
+        chain: &'a ChainConfig, 
+        contract_address: &str, 
+        api: &'a ApiDB
+    ) -> Vec<(String, Option<&'a RateLimiter>, &'a str)> {
+        let mut requests = Vec::new();
+        if let Some((etherscan_v2, chain_id)) = api.etherscan_v2_for(chain) {
+            let api_url = Parser::get_v2_api_url(etherscan_v2, chain_id, contract_address);
+            requests.push((api_url, api.etherscan_v2_limiter.as_ref(), EtherscanV2::PROVIDER));
+        }
+        let keys = match chain.api_keys.is_empty() {
+            true => vec![""],
+            false => chain.api_keys.iter().map(String::as_str).collect()
+        };
+        for key in keys {
+            let api_url = Parser::get_api_url(chain, contract_address, key);
+            requests.push((api_url, api.rate_limits.get(&chain.name), chain.name.as_str()));
+        }
+        requests
     }
 
     // get contract address from url
 
-    async fn get_contract_address(url: &str) -> Result<String, Box<dyn Error>> {
-        let addr_pattern = Regex::new(r"0x[0-9a-fA-F]{40}")?;
+    async fn get_contract_address(url: &str, options: &ParseOptions) -> Result<String, ParseError> {
+        let addr_pattern = Regex::new(r"0x[0-9a-fA-F]{40}").expect("valid address regex");
         match addr_pattern.find(url) {
             Some(address) => Ok(address.as_str().to_owned()),
             None => {
-                let address = Parser::scrape_contract_address(url).await?;
+                let address = options.retry.run(|| Parser::scrape_contract_address(url)).await?;
                 Ok(address)
             }
         }
@@ -97,153 +210,553 @@ impl Parser {
     
     // scrape contract address if no regex match
 
-    async fn scrape_contract_address(url: &str) -> Result<String, Box<dyn Error>> {
+    async fn scrape_contract_address(url: &str) -> Result<String, ParseError> {
         let response = reqwest::get(url).await?;
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(ParseError::RateLimited(format!("http status {}", response.status())))
+        }
         let body = response.text().await?;
         let document = Html::parse_document(&body);
-        let contract_name_selector = Selector::parse("#mainaddress")?;
+        let contract_name_selector = Selector::parse("#mainaddress")
+            .map_err(|why| ParseError::SelectorMiss(why.to_string()))?;
         let elems = document.select(&contract_name_selector).collect::<Vec<_>>();
         if elems.is_empty() {
-            return Err("wrong smart contract page or selector".into())
+            return Err(ParseError::AddressNotFound(url.to_owned()))
+        }
+        // scraped text ends up in output paths, so it must be an address
+
+        let addr_pattern = Regex::new(r"^0x[0-9a-fA-F]{40}$").expect("valid address regex");
+        match elems[0].text().next().map(str::trim) {
+            Some(address) if addr_pattern.is_match(address) => Ok(address.to_owned()),
+            _ => Err(ParseError::AddressNotFound(url.to_owned()))
         }
-        let address = elems[0].text().collect::<Vec<_>>()[0].trim().to_owned();
-        Ok(address)
     }
 
     // api request
 
-    async fn get_contract_data(url: &str, contract_address: &str, api: &ApiDB) -> Result<ContractData, Box::<dyn Error>> {
-        let url = Parser::get_api_url(url, contract_address, api);
-        let response = reqwest::get(url).await?;
+    async fn get_contract_data(
+        chain: &ChainConfig, 
+        contract_address: &str, 
+        api: &ApiDB, 
+        options: &ParseOptions
+    ) -> Result<ContractData, ParseError> {
+
+        // next request is tried only if previous one was rejected by explorer
+
+        let mut result = Err(ParseError::InvalidApiKey(format!("no api key for chain \"{}\"", chain.name)));
+        for (api_url, limiter, provider) in Parser::get_api_requests(chain, contract_address, api) {
+            result = options.retry.run(|| async {
+                if let Some(limiter) = limiter {
+                    limiter.acquire().await;
+                }
+                Parser::request_contract_data(&api_url, contract_address, provider).await
+            }).await;
+            if !matches!(result, Err(ParseError::InvalidApiKey(_)) | Err(ParseError::ExplorerStatus(_))) {
+                break
+            }
+        }
+        result
+    }
+
+    // single getsourcecode request, rate limits are reported as errors to retry on
+
+    async fn request_contract_data(api_url: &str, contract_address: &str, provider: &str) -> Result<ContractData, ParseError> {
+        let response = reqwest::get(api_url).await?;
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(ParseError::RateLimited(format!("http status {}", response.status())))
+        }
+        if !response.status().is_success() {
+            return Err(ParseError::ExplorerStatus(format!("http status {}", response.status())))
+        }
         let body = response.text().await?;
-        let json: serde_json::Value = serde_json::from_str(&body)?;
-        let (name, code) = (
-            json["result"][0]["ContractName"].as_str()
-                .unwrap_or("couldn't convert ContractName to string"),
-            json["result"][0]["SourceCode"].as_str()
-                .unwrap_or("couldn't convert SourceCode to string"));
-        let data = ContractData {
-            name: name.to_owned(),
-            code: code.to_owned()
-        };
-        Ok(data)
+        let json: ApiResponse = serde_json::from_str(&body)?;
+        json.into_contract_data(contract_address, provider)
     }
 
-    // get directory of splitted contract from path
+    // try source providers in configured order, first error is returned if all of them fail
 
-    fn get_dir_of_splitted_contract(file_path: &str) -> &str {
-        let chars: Vec<char> = file_path.chars().collect();
-        let start = 0;
-        let mut end = chars.len() - 1;
-        while chars[end] != '/' {
-            end -= 1;
+    async fn fetch_contract_data(
+        chain: &ChainConfig, 
+        contract_address: &str, 
+        api: &ApiDB, 
+        options: &ParseOptions
+    ) -> Result<ContractData, ParseError> {
+        let mut first_error = None;
+        for kind in &options.providers {
+            let provider = kind.provider();
+            match provider.fetch(chain, contract_address, api, options).await {
+                Ok(contract_data) => return Ok(contract_data),
+                Err(why) => {
+                    eprintln!("{} couldn't provide contract {}: {}", provider.name(), contract_address, why);
+                    first_error.get_or_insert(why);
+                }
+            }
         }
-        &file_path[start..end]
+        Err(first_error.unwrap_or_else(|| ParseError::InvalidArgs("no source providers set".to_owned())))
     }
 
-    // check if source code is splitted into separate files 
+    // folder holding address folders of one run
+
+    fn get_output_root(mode: &ParserMode) -> PathBuf {
+        match mode {
+            ParserMode::Immunefi(folder_name) => PathBuf::from(folder_name),
+            ParserMode::Single => PathBuf::new()
+        }
+    }
+
+    // folder of contract sources
+
+    fn get_contract_dir(mode: &ParserMode, contract_address: &str) -> String {
+        match mode {
+            ParserMode::Immunefi(folder_name) => format!("{}/{}", folder_name, contract_address),
+            ParserMode::Single => contract_address.to_owned()
+        }
+    }
+
+    // write manifest.json of contract
+
+    async fn save_manifest(
+        mode: &ParserMode, 
+        contract_address: &str, 
+        manifest: &ContractManifest
+    ) -> Result<(), ParseError> {
+        let dir = Parser::get_contract_dir(mode, contract_address);
+        fs::create_dir_all(&dir).await?;
+        let file_path = format!("{}/manifest.json", dir);
+        fs::write(&file_path, serde_json::to_string_pretty(manifest)?).await?;
+        println!("{} has been created!", file_path);
+        Ok(())
+    }
+
+    // write metadata.json with compiler settings and abi.json if explorer sent valid abi
+
+    async fn save_metadata(
+        mode: &ParserMode, 
+        contract_address: &str, 
+        contract_data: &ContractData
+    ) -> Result<(), ParseError> {
+        let dir = Parser::get_contract_dir(mode, contract_address);
+        fs::create_dir_all(&dir).await?;
+        let file_path = format!("{}/metadata.json", dir);
+        fs::write(&file_path, serde_json::to_string_pretty(&contract_data.metadata)?).await?;
+        println!("{} has been created!", file_path);
+        if let Some(abi) = &contract_data.abi {
+            let file_path = format!("{}/abi.json", dir);
+            fs::write(&file_path, serde_json::to_string_pretty(abi)?).await?;
+            println!("{} has been created!", file_path);
+        }
+        Ok(())
+    }
+
+    // relative path confined to contract folder, "\\" separators become "/",
+    // "." and "a/.." are resolved, absolute, drive and escaping paths are rejected
+
+    fn sanitize_source_path(path: &str) -> Result<String, ParseError> {
+        let unsafe_path = || ParseError::UnsafePath(path.to_owned());
+        let normalized = path.trim().replace('\\', "/");
+        let is_drive = normalized.as_bytes().get(1) == Some(&b':') 
+            && normalized.as_bytes()[0].is_ascii_alphabetic();
+        if normalized.starts_with('/') || is_drive || normalized.contains('\0') {
+            return Err(unsafe_path())
+        }
+        let mut components: Vec<&str> = Vec::new();
+        for component in normalized.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop().ok_or_else(unsafe_path)?;
+                }
+                component => components.push(component)
+            }
+        }
+        match components.is_empty() {
+            true => Err(unsafe_path()),
+            false => Ok(components.join("/"))
+        }
+    }
+
+    // json object of multi-file contract, etherscan sometimes wraps it in extra braces {{ }}
+
+    fn get_contract_json(code: &str) -> Option<(&str, Value)> {
+        let code = code.trim();
+        if !code.starts_with('{') {
+            return None
+        }
+        if let Ok(json @ Value::Object(_)) = serde_json::from_str(code) {
+            return Some((code, json))
+        }
+        let inner = code.strip_prefix('{')?.strip_suffix('}')?.trim();
+        match serde_json::from_str(inner) {
+            Ok(json @ Value::Object(_)) => Some((inner, json)),
+            _ => None
+        }
+    }
+
+    // check if source code is splitted into separate files and how
 
     fn get_contract_type(contract: &ContractData) -> ContractType {
-        if contract.code.starts_with('{') {
-            ContractType::Splitted
-        } else {
-            ContractType::Merged
+        match Parser::get_contract_json(&contract.code) {
+            Some((_, json)) if json["sources"].is_object() => ContractType::StandardJson,
+            Some((_, json)) if json.as_object().is_some_and(|files| {
+                !files.is_empty() && files.values().all(|file| file["content"].is_string())
+            }) => ContractType::MultiFile,
+            _ => ContractType::Merged
         }
     }
     
-    // parsing
+    // parsing, proxies are followed to their implementations which are saved side by side,
+    // returns hash of fetched sources
 
     pub async fn parse_contract(
         url: &str, 
         api: &ApiDB, 
         mode: &ParserMode, 
-        open_zeppelin: bool 
-    ) -> Result<(), Box<dyn Error>> {
+        options: &ParseOptions
+    ) -> Result<String, ParseError> {
 
         // init
 
-        let contract_address = Parser::get_contract_address(url).await?;
-        let mut contract_data = Parser::get_contract_data(url, &contract_address, api).await?;
-        let contract_type = Parser::get_contract_type(&contract_data);
+        let chain = api.find_chain(url)?;
+        let contract_address = Parser::get_contract_address(url, options).await?;
+        let mut visited = HashSet::new();
+        let mut source_hashes = Vec::new();
+        let mut next = Some((contract_address, None));
+
+        // proxy -> implementation chain, visited addresses stop cycles
 
-        // parsing
+        while let Some((contract_address, proxy)) = next.take() {
+            if !visited.insert(contract_address.to_lowercase()) {
+                eprintln!("proxy cycle detected at {}, stopping", contract_address);
+                break
+            }
+            if visited.len() > MAX_PROXY_DEPTH {
+                eprintln!("more than {} nested proxies, stopping at {}", MAX_PROXY_DEPTH, contract_address);
+                break
+            }
+
+            let contract_data = Parser::fetch_contract_data(chain, &contract_address, api, options).await?;
+            println!("{} {} fetched from {}", contract_data.name, contract_address, contract_data.provider);
+            source_hashes.push(get_content_hash(&contract_data.code));
+            Parser::save_contract_sources(&contract_data, mode, &contract_address, options).await?;
+            Parser::save_metadata(mode, &contract_address, &contract_data).await?;
 
-        match contract_type {
-            ContractType::Splitted => {
+            // explorer didn't flag a proxy, read its slots if chain has rpc endpoint
 
-                // idk why etherscan(or maybe other chains too) sometimes sends json with double curly braces {{}}
-                if contract_data.code.starts_with("{{") {
-                    contract_data.code.remove(0); 
-                    contract_data.code.pop();
+            let mut resolved = None;
+            if contract_data.implementation.is_none() && options.follow_proxies {
+                if let Some(rpc_url) = &chain.rpc_url {
+                    resolved = ProxyResolver::new(rpc_url).resolve(&contract_address).await
+                        .unwrap_or_else(|why| {
+                            eprintln!("couldn't read proxy slots of {}: {}", contract_address, why);
+                            None
+                        });
                 }
+            }
+
+            let implementation = contract_data.implementation.clone()
+                .or_else(|| resolved.as_ref().map(|resolved| resolved.implementation.clone()))
+                .filter(|_| options.follow_proxies);
+            let manifest = ContractManifest {
+                address: contract_address.clone(),
+                chain: chain.name.clone(),
+                name: contract_data.name.clone(),
+                provider: contract_data.provider.clone(),
+                proxy,
+                implementation: implementation.clone(),
+                proxy_kind: resolved.as_ref().map(|resolved| resolved.kind),
+                proxy_admin: resolved.as_ref().and_then(|resolved| resolved.admin.clone()),
+                beacon: resolved.and_then(|resolved| resolved.beacon)
+            };
+            Parser::save_manifest(mode, &contract_address, &manifest).await?;
+
+            if let Some(implementation) = implementation {
+                println!("{} is a proxy of {}", contract_address, implementation);
+                next = Some((implementation, Some(contract_address)));
+            }
+        }
+
+        // hash of fetched sources, proxies included
+
+        Ok(get_content_hash(&source_hashes.join("\n")))
+    }
+
+    // save sources of one contract as splitted tree or single file
+
+    async fn save_contract_sources(
+        contract_data: &ContractData,
+        mode: &ParserMode,
+        contract_address: &str,
+        options: &ParseOptions
+    ) -> Result<(), ParseError> {
+        let layout = options.layout;
+        let mut skipped = Vec::new();
+        let mut settings = Value::Null;
+        let mut saved_sources = Vec::new();
+        let mut all_sources = Vec::new();
+        match (Parser::get_contract_type(contract_data), Parser::get_contract_json(&contract_data.code)) {
+            (ContractType::Merged, _) | (_, None) => {
+                Parser::save_merged_contract(contract_data, mode, contract_address, layout).await?
+            }
+            (contract_type, Some((code, json))) => {
+                settings = json["settings"].clone();
 
-                // parse the json data
-                let json: Value = serde_json::from_str(&contract_data.code)?;
-                
-                // access the contract sources
-                match json["sources"].as_object() {
-                    Some(sources) => {
-                        for (path, source_info) in sources.iter() {
-                            // ignore @openzeppelin libraries and import.sol files
-                            match path {
-                                _ if path.contains("@openzeppelin") => if !open_zeppelin {continue},
-                                _ if path.contains("/import.sol") => continue,
-                                _ => {}
-                            }
-                            // accessing content(source code)
-                            match source_info["content"].as_str() {
-                                Some(source_content) => {
-                                    Parser::save_splitted_contract(mode, &contract_address, path, source_content).await?
-                                }
-                                None => eprintln!("Couldn't access \"content\" field in returned JSON of contract \"{}\" {}", contract_data.name, contract_address)
-                            }
+                // standard json input is kept verbatim for solc --standard-json
+                if json.get("language").is_some() {
+                    Parser::save_standard_input(mode, contract_address, code).await?;
+                }
+
+                // multi-file json is the sources map itself
+                let sources = match contract_type {
+                    ContractType::StandardJson => &json["sources"],
+                    _ => &json
+                };
+                for (path, source_info) in sources.as_object().into_iter().flatten() {
+                    let path = match Parser::sanitize_source_path(path) {
+                        Ok(path) => path,
+                        Err(why) => {
+                            eprintln!("Skipping source of contract \"{}\" {}: {}", contract_data.name, contract_address, why);
+                            continue
                         }
+                    };
+                    if let Some(source_content) = source_info["content"].as_str() {
+                        all_sources.push((path.clone(), source_content.to_owned()));
                     }
-                    None => eprintln!("Couldn't access \"sources\" field in returned JSON of contract \"{}\" {}", contract_data.name, contract_address)
-                } 
-            },
-            ContractType::Merged => {
-                Parser::save_merged_contract(&contract_data, mode, &contract_address).await?
+                    // include/exclude globs, by default @openzeppelin libraries and import.sol files are skipped
+                    if !options.filter.is_match(&path) {
+                        skipped.push(path);
+                        continue
+                    }
+                    // accessing content(source code)
+                    match source_info["content"].as_str() {
+                        Some(source_content) => {
+                            Parser::save_splitted_contract(mode, contract_address, &path, source_content, options).await?;
+                            saved_sources.push((path, source_content.to_owned()));
+                        }
+                        None => eprintln!("Couldn't access \"content\" field in returned JSON of contract \"{}\" {}", contract_data.name, contract_address)
+                    }
+                }
+
+                // vyper standard json keeps imported interfaces apart, as source or as abi
+                for (path, interface) in json["interfaces"].as_object().into_iter().flatten() {
+                    let path = match Parser::sanitize_source_path(path) {
+                        Ok(path) => path,
+                        Err(why) => {
+                            eprintln!("Skipping interface of contract \"{}\" {}: {}", contract_data.name, contract_address, why);
+                            continue
+                        }
+                    };
+                    if !options.filter.is_match(&path) {
+                        skipped.push(path);
+                        continue
+                    }
+                    let content = match (interface["content"].as_str(), interface.get("abi")) {
+                        (Some(content), _) => content.to_owned(),
+                        (None, Some(abi)) => serde_json::to_string_pretty(abi)?,
+                        (None, None) => {
+                            eprintln!("Couldn't access interface \"{}\" of contract \"{}\" {}", path, contract_data.name, contract_address);
+                            continue
+                        }
+                    };
+                    Parser::save_splitted_contract(mode, contract_address, &path, &content, options).await?;
+                    saved_sources.push((path, content));
+                }
+            }
+        }
+        if !skipped.is_empty() {
+            println!(
+                "skipped {} source files of \"{}\" {} by filters: {}", 
+                skipped.len(), 
+                contract_data.name, 
+                contract_address, 
+                skipped.join(", ")
+            );
+            if layout != OutputLayout::Plain {
+                eprintln!(
+                    "WARNING: {} sources skipped by filters are missing from project of \"{}\" {}, it may not compile", 
+                    skipped.len(), 
+                    contract_data.name, 
+                    contract_address
+                );
+            }
+        }
+        let all_sources: Vec<(&str, &str)> = all_sources.iter()
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+            .collect();
+        if !options.libraries.is_empty() && !all_sources.is_empty() {
+            let reports = options.libraries.fingerprint(&all_sources);
+            Parser::save_library_report(mode, contract_address, &reports).await?;
+            if options.diff_libraries {
+                Parser::save_library_diffs(mode, contract_address, &reports, &all_sources, &options.libraries).await?;
+            }
+        }
+        let sources: Vec<(&str, &str)> = saved_sources.iter()
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+            .collect();
+        match layout {
+            OutputLayout::Plain => {}
+            OutputLayout::Foundry => {
+                Parser::save_foundry_project(contract_data, mode, contract_address, &settings, &sources).await?
+            }
+            OutputLayout::Hardhat => {
+                // npm packages skipped by filters are still installed through package.json
+                Parser::save_hardhat_project(contract_data, mode, contract_address, &settings, &all_sources).await?
             }
         }
         Ok(())
     }
 
-    async fn save_splitted_contract( 
+    // libraries.json with detected library versions and modified library files
+
+    async fn save_library_report(
+        mode: &ParserMode, 
+        contract_address: &str, 
+        reports: &[LibraryReport]
+    ) -> Result<(), ParseError> {
+        if reports.is_empty() {
+            return Ok(())
+        }
+        for report in reports {
+            println!(
+                "{} vendors {} {} ({} known files, {} modified)", 
+                contract_address, 
+                report.library, 
+                report.version.as_deref().unwrap_or("of unknown version"), 
+                report.files.len(), 
+                report.modified.len()
+            );
+        }
+        // contract folder is missing when filters skipped all of its sources
+        let dir = Parser::get_contract_dir(mode, contract_address);
+        fs::create_dir_all(&dir).await?;
+        let file_path = format!("{}/libraries.json", dir);
+        fs::write(&file_path, serde_json::to_string_pretty(reports)?).await?;
+        println!("{} has been created!", file_path);
+        Ok(())
+    }
+
+    // diffs/<path>.diff of every modified library file against its closest upstream release
+
+    async fn save_library_diffs(
         mode: &ParserMode, 
+        contract_address: &str, 
+        reports: &[LibraryReport],
+        sources: &[(&str, &str)],
+        libraries: &LibraryDB
+    ) -> Result<(), ParseError> {
+        let diffs_dir = Path::new(&Parser::get_contract_dir(mode, contract_address)).join("diffs");
+        for report in reports {
+            for path in &report.modified {
+                let content = match sources.iter().find(|(source_path, _)| source_path == path) {
+                    Some((_, content)) => content,
+                    None => continue
+                };
+                let cache = Path::new(LIBRARY_CACHE_DIR);
+                match libraries.diff_upstream(cache, &report.library, path, content).await? {
+                    Some(upstream) => {
+                        let file_path = diffs_dir.join(format!("{}.diff", path));
+                        fs::create_dir_all(file_path.parent().unwrap_or(&diffs_dir)).await?;
+                        fs::write(&file_path, upstream.diff).await?;
+                        println!("{} has been created! (against {} {})", file_path.display(), report.library, upstream.version);
+                    }
+                    None => eprintln!("no cached upstream of {} {} to diff {}", report.library, path, contract_address)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // input.json next to extracted sources
+
+    async fn save_standard_input(mode: &ParserMode, contract_address: &str, code: &str) -> Result<(), ParseError> {
+        let dir = Parser::get_contract_dir(mode, contract_address);
+        fs::create_dir_all(&dir).await?;
+        let file_path = format!("{}/input.json", dir);
+        fs::write(&file_path, code).await?;
+        println!("{} has been created!", file_path);
+        Ok(())
+    }
+
+    // foundry.toml, remappings.txt and empty test folder next to src/
+
+    async fn save_foundry_project(
+        contract_data: &ContractData,
+        mode: &ParserMode,
         contract_address: &str,
-        path: &str,
-        source_content: &str
-    ) -> Result<(), Box<dyn Error>> {
+        settings: &Value,
+        sources: &[(&str, &str)]
+    ) -> Result<(), ParseError> {
+        let dir = Parser::get_contract_dir(mode, contract_address);
+        fs::create_dir_all(format!("{}/test", dir)).await?;
+
+        let file_path = format!("{}/foundry.toml", dir);
+        fs::write(&file_path, get_foundry_toml(&contract_data.metadata, settings)).await?;
+        println!("{} has been created!", file_path);
+
+        let source_paths: Vec<&str> = sources.iter().map(|(path, _)| *path).collect();
+        let remappings = get_remappings(settings, &source_paths, OutputLayout::Foundry.sources_dir());
+        let file_path = format!("{}/remappings.txt", dir);
+        fs::write(&file_path, remappings.iter().map(|remapping| format!("{}\n", remapping)).collect::<String>()).await?;
+        println!("{} has been created!", file_path);
+        Ok(())
+    }
+
+    // hardhat.config.js and package.json with npm packages imported by sources
 
-        // get directory part of the contract
+    async fn save_hardhat_project(
+        contract_data: &ContractData,
+        mode: &ParserMode,
+        contract_address: &str,
+        settings: &Value,
+        sources: &[(&str, &str)]
+    ) -> Result<(), ParseError> {
+        let dir = Parser::get_contract_dir(mode, contract_address);
+        fs::create_dir_all(format!("{}/test", dir)).await?;
+
+        if get_solc_version(&contract_data.metadata.compiler_version).is_none() {
+            eprintln!("unknown solc version \"{}\" of {}, set it in hardhat.config.js", contract_data.metadata.compiler_version, contract_address);
+        }
+        let file_path = format!("{}/hardhat.config.js", dir);
+        fs::write(&file_path, get_hardhat_config(&contract_data.metadata, settings)?).await?;
+        println!("{} has been created!", file_path);
+
+        let file_path = format!("{}/package.json", dir);
+        fs::write(&file_path, get_package_json(contract_address, sources)?).await?;
+        println!("{} has been created!", file_path);
+        Ok(())
+    }
 
-        let dir_part = Parser::get_dir_of_splitted_contract(path);
+    // path comes from sanitize_source_path, so separators are already "/"
+
+    async fn save_splitted_contract( 
+        mode: &ParserMode, 
+        contract_address: &str,
+        path: &str,
+        source_content: &str,
+        options: &ParseOptions
+    ) -> Result<(), ParseError> {
 
         // create path with immunefi name at the beginning in immunefi mode 
         // or with addr in single mode
 
-        let file_path = match mode {
-            ParserMode::Immunefi(folder_name) => format!("{}/{}/{}", folder_name, contract_address, path),
-            ParserMode::Single => format!("{}/{}", contract_address, path) 
-        };
+        let sources_dir = Path::new(&Parser::get_contract_dir(mode, contract_address)).join(options.layout.sources_dir());
+        let file_path = sources_dir.join(path);
 
-        // if directory doesn't exist, create new, otherwise overwrite
+        // shared lib/ keeps one copy of identical files, contract folder links to it
 
-        let dir = fs::metadata(&file_path).await;
-        match dir {
-            Ok(_) => fs::write(&file_path, source_content).await?,
-            Err(_) => {
-                let dir = match mode {
-                    ParserMode::Immunefi(folder_name) => format!("{}/{}/{}", folder_name, contract_address, dir_part),
-                    ParserMode::Single => format!("{}/{}", contract_address, dir_part) 
-                };
-                fs::create_dir_all(dir).await?;
-                fs::write(&file_path, source_content).await?
-            }
+        if options.dedup {
+            let stored = save_deduplicated(&Parser::get_output_root(mode), &file_path, source_content).await?;
+            println!("{} has been linked to {}", file_path.display(), stored.display());
+            return Ok(())
         }
 
-        println!("{} has been created!", file_path);
+        // create missing directories, existing file is overwritten
+
+        if let Some(dir) = file_path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        fs::write(&file_path, source_content).await?;
+
+        println!("{} has been created!", file_path.display());
 
         Ok(())
     }
@@ -252,14 +765,21 @@ impl Parser {
         contract_data: &ContractData,
         mode: &ParserMode, 
         contract_address: &str,
-    ) -> Result<(), Box<dyn Error>> {
+        layout: OutputLayout
+    ) -> Result<(), ParseError> {
 
         // create path with immunefi name at the beginning in immunefi mode 
         // or with addr in single mode
 
+        let sources_dir = layout.sources_dir();
+        let extension = contract_data.metadata.source_extension();
+        let file_name = Parser::sanitize_source_path(&format!("{}.{}", contract_data.name, extension))
+            .ok()
+            .filter(|file_name| !file_name.contains('/'))
+            .ok_or_else(|| ParseError::UnsafePath(contract_data.name.clone()))?;
         let file_path = match mode {
-            ParserMode::Immunefi(folder_name) => format!("{}/{}/{}.sol", folder_name, contract_address, contract_data.name),
-            ParserMode::Single => format!("{}/{}.sol", contract_address, contract_data.name)
+            ParserMode::Immunefi(folder_name) => format!("{}/{}/{}{}", folder_name, contract_address, sources_dir, file_name),
+            ParserMode::Single => format!("{}/{}{}", contract_address, sources_dir, file_name)
         };
 
         // if directory doesn't exist, create new, otherwise overwrite
@@ -269,8 +789,8 @@ impl Parser {
             Ok(_) => fs::write(&file_path, &contract_data.code).await?,
             Err(_) => {
                 let new_dir = match mode {
-                    ParserMode::Immunefi(folder_name) => format!("{}/{}", folder_name, contract_address),
-                    ParserMode::Single => contract_address.to_owned()
+                    ParserMode::Immunefi(folder_name) => format!("{}/{}/{}", folder_name, contract_address, sources_dir),
+                    ParserMode::Single => format!("{}/{}", contract_address, sources_dir)
                 };
                 fs::create_dir_all(new_dir).await?;
                 fs::write(&file_path, &contract_data.code).await?;
@@ -286,22 +806,372 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::error::Error;
+
+    // verified contract as explorers return it
+
+    fn get_test_contract(name: &str, code: &str) -> ContractData {
+        ContractData {
+            name: name.to_owned(),
+            code: code.to_owned(),
+            provider: "etherscan".to_owned(),
+            implementation: None,
+            metadata: ContractMetadata::default(),
+            abi: None
+        }
+    }
+
+    // output folder of one test in temp dir and parse_imm mode writing into it
+
+    fn get_test_folder(name: &str) -> (PathBuf, ParserMode) {
+        let folder = std::env::temp_dir().join(format!("iscp-{}-{}", name, std::process::id()));
+        let mode = ParserMode::Immunefi(folder.to_string_lossy().to_string());
+        (folder, mode)
+    }
 
     #[tokio::test]
     async fn get_contract_address_test() -> Result<(), Box<dyn Error>> {
         let url = "https://etherscan.io/address/0xdac17f958d2ee523a2206206994597c13d831ec7";
-        let address = Parser::get_contract_address(url).await?;
+        let address = Parser::get_contract_address(url, &ParseOptions::default()).await?;
         assert_eq!(address, "0xdac17f958d2ee523a2206206994597c13d831ec7");
         Ok(())
     }
 
+    #[test]
+    fn explorer_status_test() -> Result<(), Box<dyn Error>> {
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+
+        // rate limit
+        let json: ApiResponse = serde_json::from_str(
+            r#"{"status":"0","message":"NOTOK","result":"Max rate limit reached"}"#)?;
+        assert!(matches!(json.into_contract_data(address, "etherscan"), Err(ParseError::RateLimited(_))));
+
+        // invalid key
+        let json: ApiResponse = serde_json::from_str(
+            r#"{"status":"0","message":"NOTOK","result":"Invalid API Key"}"#)?;
+        assert!(matches!(json.into_contract_data(address, "etherscan"), Err(ParseError::InvalidApiKey(_))));
+
+        // unverified
+        let json: ApiResponse = serde_json::from_str(
+            r#"{"status":"1","message":"OK","result":[{"SourceCode":"","ABI":"Contract source code not verified","ContractName":""}]}"#)?;
+        assert!(matches!(json.into_contract_data(address, "etherscan"), Err(ParseError::Unverified(_))));
+
+        // verified
+        let json: ApiResponse = serde_json::from_str(
+            r#"{"status":"1","message":"OK","result":[{"SourceCode":"contract A {}","ABI":"[]","ContractName":"A"}]}"#)?;
+        let data = json.into_contract_data(address, "etherscan")?;
+        assert_eq!(data.name, "A");
+        assert_eq!(data.code, "contract A {}");
+
+        // metadata
+        let json: ApiResponse = serde_json::from_str(r#"{"status":"1","message":"OK","result":[{
+            "SourceCode":"contract A {}","ABI":"[{\"type\":\"fallback\"}]","ContractName":"A",
+            "CompilerVersion":"v0.8.19+commit.7dd6d404","OptimizationUsed":"1","Runs":"200",
+            "ConstructorArguments":"","EVMVersion":"Default","Library":"","LicenseType":"MIT"
+        }]}"#)?;
+        let data = json.into_contract_data(address, "etherscan")?;
+        assert_eq!(data.metadata, ContractMetadata {
+            language: "Solidity".to_owned(),
+            compiler_version: "v0.8.19+commit.7dd6d404".to_owned(),
+            optimization_used: true,
+            runs: Some(200),
+            evm_version: None,
+            license_type: Some("MIT".to_owned()),
+            constructor_arguments: None,
+            library: None
+        });
+        assert_eq!(data.abi.unwrap()[0]["type"], "fallback");
+        Ok(())
+    }
+
+    #[test]
+    fn blockscout_sources_test() -> Result<(), Box<dyn Error>> {
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let json: ApiResponse = serde_json::from_str(r#"{"status":"1","message":"OK","result":[{
+            "SourceCode": "import \"./lib/Math.sol\"; contract Token {}",
+            "ContractName": "Token",
+            "FileName": "contracts/Token.sol",
+            "ABI": "[]",
+            "AdditionalSources": [
+                {"Filename": "contracts/lib/Math.sol", "SourceCode": "library Math {}"}
+            ]
+        }]}"#)?;
+        let data = json.into_contract_data(address, "blockscout-eth")?;
+        assert_eq!(data.provider, "blockscout-eth");
+        assert_eq!(Parser::get_contract_type(&data), ContractType::StandardJson);
+
+        let code: Value = serde_json::from_str(&data.code)?;
+        assert_eq!(code["sources"]["contracts/lib/Math.sol"]["content"], "library Math {}");
+        assert!(code["sources"]["contracts/Token.sol"]["content"].as_str().unwrap().ends_with("contract Token {}"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn standard_input_test() -> Result<(), Box<dyn Error>> {
+        let (folder, mode) = get_test_folder("standard-input");
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let input = r#"{"language":"Solidity","sources":{"contracts/Token.sol":{"content":"contract Token {}"}},"settings":{"viaIR":true,"optimizer":{"enabled":true,"runs":1}}}"#;
+        let data = get_test_contract("Token", &format!("{{{}}}", input));
+        let options = ParseOptions { layout: OutputLayout::Foundry, ..Default::default() };
+        Parser::save_contract_sources(&data, &mode, address, &options).await?;
+
+        let dir = folder.join(address);
+        assert_eq!(fs::read_to_string(dir.join("input.json")).await?, input);
+        assert_eq!(fs::read_to_string(dir.join("src/contracts/Token.sol")).await?, "contract Token {}");
+        assert!(fs::read_to_string(dir.join("foundry.toml")).await?.contains("via_ir = true"));
+        assert_eq!(fs::read_to_string(dir.join("remappings.txt")).await?, "contracts/=src/contracts/\n");
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hardhat_dependencies_test() -> Result<(), Box<dyn Error>> {
+        let (folder, mode) = get_test_folder("hardhat");
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let data = get_test_contract(
+            "Vault",
+            r#"{"contracts/Vault.sol":{"content":"import \"@openzeppelin/contracts/access/Ownable.sol\";"},"@openzeppelin/contracts/access/Ownable.sol":{"content":"// OpenZeppelin Contracts (last updated v4.9.0) (access/Ownable.sol)"}}"#
+        );
+        // cli drops default excludes for hardhat layout, so openzeppelin is saved and listed
+        let options = ParseOptions {
+            layout: OutputLayout::Hardhat,
+            filter: SourceFilter::new(&[], &[])?,
+            ..Default::default()
+        };
+        Parser::save_contract_sources(&data, &mode, address, &options).await?;
+        let dir = folder.join(address);
+        assert!(fs::metadata(dir.join("contracts/contracts/Vault.sol")).await.is_ok());
+        assert!(fs::metadata(dir.join("contracts/@openzeppelin/contracts/access/Ownable.sol")).await.is_ok());
+        let package: Value = serde_json::from_str(&fs::read_to_string(dir.join("package.json")).await?)?;
+        assert_eq!(package["dependencies"]["@openzeppelin/contracts"], "4.9.0");
+
+        // package excluded with --exclude still comes from npm
+        let address = "0x5041ed759dd4afc3a72b8192c143f72f4724081a";
+        let options = ParseOptions {
+            layout: OutputLayout::Hardhat,
+            filter: SourceFilter::new(&[], &["@openzeppelin/**".to_owned()])?,
+            ..Default::default()
+        };
+        Parser::save_contract_sources(&data, &mode, address, &options).await?;
+        let dir = folder.join(address);
+        assert!(fs::metadata(dir.join("contracts/@openzeppelin")).await.is_err());
+        let package: Value = serde_json::from_str(&fs::read_to_string(dir.join("package.json")).await?)?;
+        assert_eq!(package["dependencies"]["@openzeppelin/contracts"], "4.9.0");
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn vyper_sources_test() -> Result<(), Box<dyn Error>> {
+        let (folder, mode) = get_test_folder("vyper");
+        let address = "0xbebc44782c7db0a1a60cb6fe97d0b483032ff1c7";
+        let json: ApiResponse = serde_json::from_str(r##"{"status":"1","message":"OK","result":[{
+            "SourceCode":"# @version 0.2.4\n@external\ndef foo(): pass","ABI":"[]","ContractName":"StableSwap",
+            "CompilerVersion":"vyper:0.2.4"
+        }]}"##)?;
+        let data = json.into_contract_data(address, "etherscan")?;
+        assert!(data.metadata.is_vyper());
+        assert_eq!(Parser::get_contract_type(&data), ContractType::Merged);
+        Parser::save_contract_sources(&data, &mode, address, &ParseOptions::default()).await?;
+        assert!(fs::metadata(folder.join(address).join("StableSwap.vy")).await.is_ok());
+
+        let input = r#"{"language":"Vyper","sources":{"contracts/Vault.vy":{"content":"import interfaces.Token as Token"}},
+            "interfaces":{"interfaces/Token.vy":{"content":"@external\ndef transfer(): pass"},"interfaces/Oracle.json":{"abi":[]}}}"#;
+        let data = ContractData {
+            metadata: ContractMetadata { compiler_version: "vyper:0.3.7".to_owned(), ..Default::default() },
+            ..get_test_contract("Vault", input)
+        };
+        Parser::save_contract_sources(&data, &mode, address, &ParseOptions::default()).await?;
+        let dir = folder.join(address);
+        assert!(fs::metadata(dir.join("contracts/Vault.vy")).await.is_ok());
+        assert_eq!(fs::read_to_string(dir.join("interfaces/Token.vy")).await?, "@external\ndef transfer(): pass");
+        assert_eq!(fs::read_to_string(dir.join("interfaces/Oracle.json")).await?, "[]");
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn save_splitted_contract_test() -> Result<(), Box<dyn Error>> {
+        let (folder, mode) = get_test_folder("splitted");
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let paths = ["Token.sol", "contracts/nested/Vault.sol", "библиотека/Математика.sol", "ß/€.sol", "interfaces\\IToken.sol"];
+        for path in paths {
+            let sanitized = Parser::sanitize_source_path(path)?;
+            Parser::save_splitted_contract(&mode, address, &sanitized, path, &ParseOptions::default()).await?;
+            // second write overwrites
+            Parser::save_splitted_contract(&mode, address, &sanitized, path, &ParseOptions::default()).await?;
+        }
+        let dir = folder.join(address);
+        assert_eq!(fs::read_to_string(dir.join("Token.sol")).await?, "Token.sol");
+        assert_eq!(fs::read_to_string(dir.join("contracts/nested/Vault.sol")).await?, "contracts/nested/Vault.sol");
+        assert_eq!(fs::read_to_string(dir.join("библиотека/Математика.sol")).await?, "библиотека/Математика.sol");
+        assert_eq!(fs::read_to_string(dir.join("ß/€.sol")).await?, "ß/€.sol");
+        assert_eq!(fs::read_to_string(dir.join("interfaces/IToken.sol")).await?, "interfaces\\IToken.sol");
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn sanitize_source_path_test() {
+        let safe = [
+            ("contracts/Token.sol", "contracts/Token.sol"),
+            ("./contracts//Token.sol", "contracts/Token.sol"),
+            ("contracts/lib/../Token.sol", "contracts/Token.sol"),
+            ("contracts\\lib\\Math.sol", "contracts/lib/Math.sol"),
+            ("@openzeppelin/contracts/token/ERC20.sol", "@openzeppelin/contracts/token/ERC20.sol")
+        ];
+        for (path, expected) in safe {
+            assert_eq!(Parser::sanitize_source_path(path).unwrap(), expected);
+        }
+        let traversal = [
+            "../../.bashrc",
+            "contracts/../../escape.sol",
+            "..\\..\\escape.sol",
+            "/etc/passwd",
+            "\\\\server\\share\\a.sol",
+            "C:\\Windows\\system32\\a.sol",
+            "c:/a.sol",
+            "contracts/..",
+            "",
+            "./",
+            "a\0.sol"
+        ];
+        for path in traversal {
+            assert!(matches!(Parser::sanitize_source_path(path), Err(ParseError::UnsafePath(_))), "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn unsafe_sources_test() -> Result<(), Box<dyn Error>> {
+        let (folder, _) = get_test_folder("unsafe");
+        let mode = ParserMode::Immunefi(folder.join("out").to_string_lossy().to_string());
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let mut data = get_test_contract(
+            "A",
+            r#"{"../../evil.sol":{"content":"evil"},"/tmp/evil.sol":{"content":"evil"},"ok/A.sol":{"content":"contract A {}"},"@openzeppelin/contracts/Ownable.sol":{"content":"contract Ownable {}"}}"#
+        );
+        Parser::save_contract_sources(&data, &mode, address, &ParseOptions::default()).await?;
+        assert!(fs::metadata(folder.join("out").join(address).join("ok/A.sol")).await.is_ok());
+        assert!(fs::metadata(folder.join("evil.sol")).await.is_err());
+        // default filters skip openzeppelin
+        assert!(fs::metadata(folder.join("out").join(address).join("@openzeppelin")).await.is_err());
+
+        data.code = "contract A {}".to_owned();
+        data.name = "../../evil".to_owned();
+        let result = Parser::save_contract_sources(&data, &mode, address, &ParseOptions::default()).await;
+        assert!(matches!(result, Err(ParseError::UnsafePath(_))));
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn library_report_test() -> Result<(), Box<dyn Error>> {
+        let (folder, mode) = get_test_folder("libraries");
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let mut options = ParseOptions::default();
+        options.libraries.libraries.push(LibraryRelease {
+            name: "openzeppelin-contracts".to_owned(),
+            version: "4.9.0".to_owned(),
+            files: [("access/Ownable.sol".to_owned(), get_source_hash("contract Ownable {}"))].into_iter().collect()
+        });
+        let data = get_test_contract(
+            "Vault",
+            r#"{"contracts/Vault.sol":{"content":"contract Vault {}"},"@openzeppelin/contracts/access/Ownable.sol":{"content":"contract Ownable {}"}}"#
+        );
+        Parser::save_contract_sources(&data, &mode, address, &options).await?;
+
+        // openzeppelin is fingerprinted even though default filters don't save it
+        let report: Value = serde_json::from_str(&fs::read_to_string(folder.join(address).join("libraries.json")).await?)?;
+        assert_eq!(report[0]["library"], "openzeppelin-contracts");
+        assert_eq!(report[0]["version"], "4.9.0");
+        assert_eq!(report[0]["files"][0], "@openzeppelin/contracts/access/Ownable.sol");
+
+        // proxy made only of openzeppelin sources still gets its report
+        let proxy = "0x5041ed759dd4afc3a72b8192c143f72f4724081a";
+        let data = get_test_contract(
+            "TransparentUpgradeableProxy",
+            r#"{"@openzeppelin/contracts/access/Ownable.sol":{"content":"contract Ownable {}"}}"#
+        );
+        Parser::save_contract_sources(&data, &mode, proxy, &options).await?;
+        let report: Value = serde_json::from_str(&fs::read_to_string(folder.join(proxy).join("libraries.json")).await?)?;
+        assert_eq!(report[0]["version"], "4.9.0");
+        assert!(fs::metadata(folder.join(proxy).join("@openzeppelin")).await.is_err());
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+
+    #[test]
+    fn contract_type_test() {
+        let cases = [
+            ("pragma solidity ^0.8.0; contract A {}", ContractType::Merged),
+            ("{ /* not json */ } contract A {}", ContractType::Merged),
+            ("{\"name\": \"not sources\"}", ContractType::Merged),
+            ("  \n{\"A.sol\": {\"content\": \"contract A {}\"}}", ContractType::MultiFile),
+            ("{{\"language\":\"Solidity\",\"sources\":{\"A.sol\":{\"content\":\"contract A {}\"}},\"settings\":{}}}", ContractType::StandardJson),
+            ("\r\n{\"language\":\"Solidity\",\"sources\":{}}\r\n", ContractType::StandardJson)
+        ];
+        for (code, expected) in cases {
+            assert_eq!(Parser::get_contract_type(&get_test_contract("A", code)), expected, "{}", code);
+        }
+        let (inner, json) = Parser::get_contract_json("{{\"sources\":{}}}").unwrap();
+        assert_eq!(inner, "{\"sources\":{}}");
+        assert!(json["sources"].is_object());
+    }
+
+    #[test]
+    fn proxy_implementation_test() -> Result<(), Box<dyn Error>> {
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let response = |implementation: &str| format!(
+            r#"{{"status":"1","message":"OK","result":[{{"SourceCode":"contract Proxy {{}}","ContractName":"Proxy","ABI":"[]","Proxy":"1","Implementation":"{}"}}]}}"#,
+            implementation
+        );
+
+        let json: ApiResponse = serde_json::from_str(&response("0x5041ed759dd4afc3a72b8192c143f72f4724081a"))?;
+        let data = json.into_contract_data(address, "etherscan")?;
+        assert_eq!(data.implementation.as_deref(), Some("0x5041ed759dd4afc3a72b8192c143f72f4724081a"));
+
+        // empty, zero, self and malformed addresses are not implementations
+        for implementation in ["", "0x0000000000000000000000000000000000000000", address, "日本"] {
+            let json: ApiResponse = serde_json::from_str(&response(implementation))?;
+            assert_eq!(json.into_contract_data(address, "etherscan")?.implementation, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn api_requests_test() -> Result<(), Box<dyn Error>> {
+        let address = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        let mut db = ApiDB::new();
+        db.etherscan_v2 = Some(EtherscanV2 { api_key: "v2_key".to_owned(), rate_limit: 5.0 });
+        let mut chain = ChainConfig::new("arbiscan", "key", "https://api.arbiscan.io");
+
+        // no chain id, only explorer
+        let requests = Parser::get_api_requests(&chain, address, &db);
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].0.starts_with("https://api.arbiscan.io/api?"));
+        assert_eq!(requests[0].2, "arbiscan");
+
+        // multichain api first, explorer as fallback
+        chain.chain_id = Some(42161);
+        chain.etherscan_v2 = true;
+        let requests = Parser::get_api_requests(&chain, address, &db);
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].0, format!(
+            "https://api.etherscan.io/v2/api?chainid=42161&module=contract&action=getsourcecode&address={}&apikey=v2_key", 
+            address
+        ));
+        assert!(requests[1].0.ends_with("&apikey=key"));
+        assert_eq!((requests[0].2, requests[1].2), ("etherscan-v2", "arbiscan"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn parse_single_contract_test() -> Result<(), Box<dyn Error>> {
         let mut db = ApiDB::new();
         db.read().await?;
         let mode = ParserMode::Single;
         let url = "https://etherscan.io/address/0xdac17f958d2ee523a2206206994597c13d831ec7";
-        Parser::parse_contract(url, &db, &mode, false).await?;
+        Parser::parse_contract(url, &db, &mode, &ParseOptions::default()).await?;
         Ok(())
     }
 
@@ -311,7 +1181,7 @@ mod tests {
         db.read().await?;
         let url = "https://immunefi.com/bounty/sushiswap/";
         let folder_name = "sushi swap";
-        Parser::immunefi_traverse(url, &db, false, folder_name, 2).await?;
+        Parser::immunefi_traverse(url, &db, folder_name, Some(2), &ParseOptions::default()).await?;
         Ok(())
     }
 }
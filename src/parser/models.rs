@@ -1,15 +1,383 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::{LibraryDB, OutputLayout, ParseError, ProviderKind, ProxyKind, RetryPolicy, SourceFilter};
+
 #[derive(Debug)]
 pub struct ContractData {
     pub name: String,
-    pub code: String
+    pub code: String,
+    pub provider: String,
+    pub implementation: Option<String>,
+    pub metadata: ContractMetadata,
+    pub abi: Option<Value>
+}
+
+// compiler settings and deployment details, written as metadata.json
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ContractMetadata {
+    pub language: String,
+    pub compiler_version: String,
+    pub optimization_used: bool,
+    pub runs: Option<u64>,
+    pub evm_version: Option<String>,
+    pub license_type: Option<String>,
+    pub constructor_arguments: Option<String>,
+    pub library: Option<String>
+}
+
+impl ContractMetadata {
+
+    // vyper compiler versions look like "vyper:0.3.7"
+
+    pub fn is_vyper(&self) -> bool {
+        self.language.eq_ignore_ascii_case("vyper") 
+            || self.compiler_version.trim().to_lowercase().starts_with("vyper")
+    }
+
+    pub fn source_extension(&self) -> &'static str {
+        match self.is_vyper() {
+            true => "vy",
+            false => "sol"
+        }
+    }
 }
 
+// plain source, etherscan multi-file json { "a.sol": { content } }
+// or standard json input with language/sources/settings
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ContractType {
-    Splitted,
-    Merged
+    Merged,
+    MultiFile,
+    StandardJson
 }
 
 pub enum ParserMode {
     Single,
     Immunefi(String)
-}
\ No newline at end of file
+}
+
+// settings shared by every contract of one run
+
+#[derive(Debug)]
+pub struct ParseOptions {
+    pub retry: RetryPolicy,
+    pub providers: Vec<ProviderKind>,
+    pub follow_proxies: bool,
+    pub layout: OutputLayout,
+    pub filter: SourceFilter,
+    pub dedup: bool,
+    pub libraries: LibraryDB,
+    pub diff_libraries: bool,
+    pub force: bool
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            retry: RetryPolicy::default(),
+            providers: vec![ProviderKind::Etherscan, ProviderKind::Sourcify],
+            follow_proxies: true,
+            layout: OutputLayout::Plain,
+            filter: SourceFilter::default(),
+            dedup: false,
+            libraries: LibraryDB::default(),
+            diff_libraries: false,
+            force: false
+        }
+    }
+}
+
+// manifest.json written next to sources of every contract
+
+#[derive(Debug, Serialize)]
+pub struct ContractManifest {
+    pub address: String,
+    pub chain: String,
+    pub name: String,
+    pub provider: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub implementation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_kind: Option<ProxyKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_admin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub beacon: Option<String>
+}
+
+// etherscan-style response envelope, result is a string when status is "0"
+
+#[derive(Debug, Deserialize)]
+pub struct ApiResponse {
+    pub status: String,
+    pub message: String,
+    pub result: Value
+}
+
+// one entry of getsourcecode result
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SourceCodeResult {
+    #[serde(default)]
+    pub source_code: String,
+    #[serde(default)]
+    pub contract_name: String,
+    #[serde(default, rename = "ABI")]
+    pub abi: String,
+    #[serde(default)]
+    pub file_name: String,
+    #[serde(default)]
+    pub additional_sources: Vec<AdditionalSource>,
+    #[serde(default, alias = "ImplementationAddress")]
+    pub implementation: String,
+    #[serde(default)]
+    pub compiler_version: String,
+    #[serde(default)]
+    pub optimization_used: Value,
+    #[serde(default, alias = "OptimizationRuns")]
+    pub runs: Value,
+    #[serde(default, rename = "EVMVersion")]
+    pub evm_version: String,
+    #[serde(default)]
+    pub license_type: String,
+    #[serde(default)]
+    pub constructor_arguments: String,
+    #[serde(default)]
+    pub library: String
+}
+
+// blockscout returns main file in SourceCode and the rest of multi-file contract here
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AdditionalSource {
+    pub filename: String,
+    pub source_code: String
+}
+
+// explorers send numbers and flags either as strings or as json values
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.trim().to_owned(),
+        Value::Null => String::new(),
+        other => other.to_string()
+    }
+}
+
+// empty strings and placeholders are not set
+
+fn non_empty(text: &str) -> Option<String> {
+    match text.trim() {
+        "" | "Default" | "default" | "None" => None,
+        text => Some(text.to_owned())
+    }
+}
+
+impl SourceCodeResult {
+
+    // compiler settings of verified contract
+
+    fn get_metadata(&self) -> ContractMetadata {
+        let optimization_used = matches!(
+            value_to_string(&self.optimization_used).to_lowercase().as_str(),
+            "1" | "true"
+        );
+        let language = match self.compiler_version.trim().to_lowercase().starts_with("vyper") {
+            true => "Vyper",
+            false => "Solidity"
+        };
+        ContractMetadata {
+            language: language.to_owned(),
+            compiler_version: self.compiler_version.trim().to_owned(),
+            optimization_used,
+            runs: value_to_string(&self.runs).parse().ok(),
+            evm_version: non_empty(&self.evm_version),
+            license_type: non_empty(&self.license_type),
+            constructor_arguments: non_empty(&self.constructor_arguments),
+            library: non_empty(&self.library)
+        }
+    }
+
+    // implementation address reported by explorer, empty and zero addresses mean no proxy
+
+    fn get_implementation(&self, contract_address: &str) -> Option<String> {
+        let implementation = self.implementation.trim();
+        let is_address = implementation.len() == 42 
+            && implementation.starts_with("0x") 
+            && implementation[2..].chars().all(|c| c.is_ascii_hexdigit());
+        let is_zero = is_address && implementation[2..].chars().all(|c| c == '0');
+        match is_address && !is_zero && !implementation.eq_ignore_ascii_case(contract_address) {
+            true => Some(implementation.to_owned()),
+            false => None
+        }
+    }
+
+    // blockscout multi-file contract to etherscan-style json with sources, 
+    // so it is saved as a splitted tree
+
+    fn into_code(self) -> String {
+        if self.additional_sources.is_empty() {
+            return self.source_code
+        }
+        let main_file = match self.file_name.is_empty() {
+            true => format!("{}.sol", self.contract_name),
+            false => self.file_name
+        };
+        let mut sources = Map::new();
+        sources.insert(main_file, json!({ "content": self.source_code }));
+        for source in self.additional_sources {
+            sources.entry(source.filename).or_insert(json!({ "content": source.source_code }));
+        }
+        json!({ "sources": sources }).to_string()
+    }
+}
+
+impl ApiResponse {
+
+    // map explorer status to contract data or to a specific error, 
+    // provider names the explorer or api the response came from
+
+    pub fn into_contract_data(self, contract_address: &str, provider: &str) -> Result<ContractData, ParseError> {
+        if self.status != "1" {
+            let details = match &self.result {
+                Value::String(text) => text.to_owned(),
+                other => other.to_string()
+            };
+            let lowercase = details.to_lowercase();
+            return Err(match lowercase {
+                _ if lowercase.contains("rate limit") => ParseError::RateLimited(details),
+                _ if lowercase.contains("invalid api key") 
+                    || lowercase.contains("missing/invalid api key") => ParseError::InvalidApiKey(details),
+                _ if lowercase.contains("not verified") => ParseError::Unverified(contract_address.to_owned()),
+                _ => ParseError::ExplorerStatus(format!("{}: {}", self.message, details))
+            })
+        }
+        let mut results: Vec<SourceCodeResult> = serde_json::from_value(self.result)?;
+        if results.is_empty() {
+            return Err(ParseError::Json(format!("empty result for contract {}", contract_address)))
+        }
+        let result = results.swap_remove(0);
+        if result.source_code.is_empty() || result.abi == "Contract source code not verified" {
+            return Err(ParseError::Unverified(contract_address.to_owned()))
+        }
+        let name = result.contract_name.clone();
+        let implementation = result.get_implementation(contract_address);
+        let metadata = result.get_metadata();
+        let abi = serde_json::from_str(&result.abi).ok();
+        Ok(ContractData {
+            name,
+            code: result.into_code(),
+            provider: provider.to_owned(),
+            implementation,
+            metadata,
+            abi
+        })
+    }
+}
+
+// one chain of api database
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainConfig {
+    pub name: String,
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    pub api_url: String,
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    #[serde(default)]
+    pub etherscan_v2: bool,
+    #[serde(default)]
+    pub rpc_url: Option<String>,
+    #[serde(default = "ChainConfig::default_rate_limit")]
+    pub rate_limit: f64,
+    #[serde(default = "ChainConfig::default_enabled")]
+    pub enabled: bool
+}
+
+impl ChainConfig {
+
+    // requests per second of free etherscan-like plans
+
+    pub const DEFAULT_RATE_LIMIT: f64 = 5.0;
+
+    // init with single key and default budget
+
+    pub fn new(name: &str, api_key: &str, api_url: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            hosts: Vec::new(),
+            api_url: api_url.to_owned(),
+            api_keys: vec![api_key.to_owned()],
+            chain_id: None,
+            etherscan_v2: false,
+            rpc_url: None,
+            rate_limit: Self::DEFAULT_RATE_LIMIT,
+            enabled: true
+        }
+    }
+
+    // explicit host patterns, or host of api url without "api." / "api-" prefix
+    // e.g. "https://api-optimistic.etherscan.io" -> "optimistic.etherscan.io"
+
+    pub fn host_patterns(&self) -> Vec<String> {
+        if !self.hosts.is_empty() {
+            return self.hosts.iter().map(|host| host.to_lowercase()).collect()
+        }
+        reqwest::Url::parse(&self.api_url).ok()
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .map(|host| {
+                let host = host.strip_prefix("api.")
+                    .or_else(|| host.strip_prefix("api-"))
+                    .unwrap_or(&host);
+                vec![host.to_owned()]
+            })
+            .unwrap_or_default()
+    }
+
+    // length of the most specific pattern matching host, "*.x.io" matches subdomains only
+
+    pub fn match_host(&self, host: &str) -> Option<usize> {
+        self.host_patterns().iter()
+            .filter(|pattern| match pattern.strip_prefix("*.") {
+                Some(domain) => host.ends_with(&format!(".{}", domain)),
+                None => host == pattern.as_str()
+            })
+            .map(|pattern| pattern.len())
+            .max()
+    }
+
+    fn default_rate_limit() -> f64 {
+        Self::DEFAULT_RATE_LIMIT
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+// single key for etherscan multichain api, chains are selected by chain id
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EtherscanV2 {
+    pub api_key: String,
+    #[serde(default = "ChainConfig::default_rate_limit")]
+    pub rate_limit: f64
+}
+
+impl EtherscanV2 {
+    pub const API_URL: &'static str = "https://api.etherscan.io/v2/api";
+
+    // provider recorded in manifest.json for sources fetched through multichain api
+
+    pub const PROVIDER: &'static str = "etherscan-v2";
+}
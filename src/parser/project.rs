@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use super::{ContractMetadata, ParseError};
+
+// how sources of a contract are laid out on disk
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputLayout {
+    Plain,
+    Foundry,
+    Hardhat
+}
+
+impl OutputLayout {
+
+    // prefix of source paths inside contract folder
+
+    pub fn sources_dir(&self) -> &'static str {
+        match self {
+            OutputLayout::Plain => "",
+            OutputLayout::Foundry => "src/",
+            OutputLayout::Hardhat => "contracts/"
+        }
+    }
+}
+
+impl FromStr for OutputLayout {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "plain" => Ok(OutputLayout::Plain),
+            "foundry" => Ok(OutputLayout::Foundry),
+            "hardhat" => Ok(OutputLayout::Hardhat),
+            other => Err(ParseError::InvalidArgs(format!("unknown output layout \"{}\"", other)))
+        }
+    }
+}
+
+// "v0.8.19+commit.7dd6d404" -> "0.8.19", none for nightly or non-solc versions
+
+pub fn get_solc_version(compiler_version: &str) -> Option<String> {
+    let version = compiler_version.trim().trim_start_matches('v');
+    let version = version.split(['+', '-']).next().unwrap_or_default();
+    let parts: Vec<&str> = version.split('.').collect();
+    match parts.len() == 3 && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())) {
+        true => Some(version.to_owned()),
+        false => None
+    }
+}
+
+// remappings of standard json pointed into src/, plus one remapping per top-level
+// source folder so non-relative imports like "@openzeppelin/..." resolve
+
+pub fn get_remappings(settings: &Value, source_paths: &[&str], sources_dir: &str) -> Vec<String> {
+    let mut remappings: Vec<String> = Vec::new();
+    for remapping in settings["remappings"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+        let remapping = match remapping.split_once('=') {
+            Some((prefix, target)) if !target.starts_with('/') => {
+                format!("{}={}{}", prefix, sources_dir, target.trim_start_matches("./"))
+            }
+            _ => remapping.to_owned()
+        };
+        if !remappings.contains(&remapping) {
+            remappings.push(remapping);
+        }
+    }
+
+    let mut folders: Vec<&str> = source_paths.iter()
+        .filter_map(|path| path.split_once('/').map(|(folder, _)| folder))
+        .filter(|folder| !folder.is_empty() && *folder != "." && *folder != "..")
+        .collect();
+    folders.sort_unstable();
+    folders.dedup();
+    for folder in folders {
+        let prefix = format!("{}/", folder);
+        let is_remapped = remappings.iter().any(|remapping| {
+            let context_free = remapping.split_once(':').map_or(remapping.as_str(), |(_, rest)| rest);
+            context_free.starts_with(&format!("{}=", prefix))
+        });
+        if !is_remapped {
+            remappings.push(format!("{}={}{}", prefix, sources_dir, prefix));
+        }
+    }
+    remappings
+}
+
+// foundry.toml pinned to compiler settings of verified contract
+
+pub fn get_foundry_toml(metadata: &ContractMetadata, settings: &Value) -> String {
+    let mut config = vec![
+        "[profile.default]".to_owned(),
+        "src = \"src\"".to_owned(),
+        "test = \"test\"".to_owned(),
+        "out = \"out\"".to_owned(),
+        "libs = [\"lib\"]".to_owned()
+    ];
+    if let Some(version) = get_solc_version(&metadata.compiler_version) {
+        config.push(format!("solc_version = \"{}\"", version));
+        config.push("auto_detect_solc = false".to_owned());
+    }
+    let optimizer = settings["optimizer"]["enabled"].as_bool().unwrap_or(metadata.optimization_used);
+    config.push(format!("optimizer = {}", optimizer));
+    if let Some(runs) = settings["optimizer"]["runs"].as_u64().or(metadata.runs) {
+        config.push(format!("optimizer_runs = {}", runs));
+    }
+    let evm_version = settings["evmVersion"].as_str().map(str::to_owned).or_else(|| metadata.evm_version.clone());
+    if let Some(evm_version) = evm_version {
+        config.push(format!("evm_version = \"{}\"", evm_version.to_lowercase()));
+    }
+    if settings["viaIR"].as_bool().unwrap_or(false) {
+        config.push("via_ir = true".to_owned());
+    }
+    config.join("\n") + "\n"
+}
+
+// solidity section of hardhat config, same compiler settings as foundry.toml
+
+pub fn get_hardhat_config(metadata: &ContractMetadata, settings: &Value) -> Result<String, ParseError> {
+    let mut compiler = Map::new();
+    if let Some(version) = get_solc_version(&metadata.compiler_version) {
+        compiler.insert("version".to_owned(), json!(version));
+    }
+    let mut compiler_settings = Map::new();
+    compiler_settings.insert("optimizer".to_owned(), json!({
+        "enabled": settings["optimizer"]["enabled"].as_bool().unwrap_or(metadata.optimization_used),
+        "runs": settings["optimizer"]["runs"].as_u64().or(metadata.runs).unwrap_or(200)
+    }));
+    let evm_version = settings["evmVersion"].as_str().map(str::to_owned).or_else(|| metadata.evm_version.clone());
+    if let Some(evm_version) = evm_version {
+        compiler_settings.insert("evmVersion".to_owned(), json!(evm_version.to_lowercase()));
+    }
+    if settings["viaIR"].as_bool().unwrap_or(false) {
+        compiler_settings.insert("viaIR".to_owned(), json!(true));
+    }
+    compiler.insert("settings".to_owned(), Value::Object(compiler_settings));
+    Ok(format!(
+        "/** @type import('hardhat/config').HardhatUserConfig */\nmodule.exports = {{\n  solidity: {}\n}};\n",
+        serde_json::to_string_pretty(&compiler)?.replace('\n', "\n  ")
+    ))
+}
+
+// npm packages of "@scope/name/..." source paths, openzeppelin versions taken from 
+// "OpenZeppelin Contracts (last updated vX.Y.Z)" headers when present, other
+// packages may vendor openzeppelin files, so their headers say nothing
+
+const OPENZEPPELIN_PACKAGES: &[&str] = &["@openzeppelin/contracts", "@openzeppelin/contracts-upgradeable"];
+
+pub fn get_npm_dependencies(sources: &[(&str, &str)]) -> BTreeMap<String, String> {
+    let version_header = Regex::new(r"OpenZeppelin Contracts(?: \(last updated)? v(\d+\.\d+\.\d+)").unwrap();
+    let mut versions: BTreeMap<String, Option<(u64, u64, u64)>> = BTreeMap::new();
+    for (path, content) in sources {
+        let mut parts = path.split('/');
+        let package = match (parts.next(), parts.next(), parts.next()) {
+            (Some(scope), Some(name), Some(_)) if scope.len() > 1 && scope.starts_with('@') && !name.is_empty() => {
+                format!("{}/{}", scope, name)
+            }
+            _ => continue
+        };
+        let version = version_header.captures(content)
+            .filter(|_| OPENZEPPELIN_PACKAGES.contains(&package.as_str()))
+            .and_then(|captures| {
+                let mut numbers = captures[1].split('.').map(|number| number.parse::<u64>().unwrap_or(0));
+                Some((numbers.next()?, numbers.next()?, numbers.next()?))
+            });
+        let entry = versions.entry(package).or_insert(None);
+        if version > *entry {
+            *entry = version;
+        }
+    }
+    versions.into_iter()
+        .map(|(package, version)| {
+            let version = match version {
+                Some((major, minor, patch)) => format!("{}.{}.{}", major, minor, patch),
+                None => "*".to_owned()
+            };
+            (package, version)
+        })
+        .collect()
+}
+
+pub fn get_package_json(contract_address: &str, sources: &[(&str, &str)]) -> Result<String, ParseError> {
+    let package = json!({
+        "name": contract_address.to_lowercase(),
+        "private": true,
+        "scripts": { "build": "hardhat compile", "test": "hardhat test" },
+        "dependencies": get_npm_dependencies(sources),
+        "devDependencies": { "hardhat": "^2.22.0" }
+    });
+    Ok(serde_json::to_string_pretty(&package)? + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn foundry_project_test() {
+        assert_eq!(get_solc_version("v0.8.19+commit.7dd6d404").as_deref(), Some("0.8.19"));
+        assert_eq!(get_solc_version("v0.4.24-nightly.2018.5.16+commit.7f965c86").as_deref(), Some("0.4.24"));
+        assert_eq!(get_solc_version("vyper:0.3.7"), None);
+
+        let settings = json!({
+            "remappings": ["@oz/=lib/openzeppelin-contracts/", "ds-test/=/abs/ds-test/"],
+            "optimizer": { "enabled": true, "runs": 10000 },
+            "evmVersion": "london"
+        });
+        let paths = ["lib/openzeppelin-contracts/token/ERC20.sol", "@oz/utils/Address.sol", "contracts/Vault.sol", "Main.sol"];
+        assert_eq!(get_remappings(&settings, &paths, "src/"), vec![
+            "@oz/=src/lib/openzeppelin-contracts/",
+            "ds-test/=/abs/ds-test/",
+            "contracts/=src/contracts/",
+            "lib/=src/lib/"
+        ]);
+
+        let metadata = ContractMetadata {
+            compiler_version: "v0.8.19+commit.7dd6d404".to_owned(),
+            optimization_used: true,
+            runs: Some(200),
+            ..Default::default()
+        };
+        let config = get_foundry_toml(&metadata, &settings);
+        assert!(config.contains("solc_version = \"0.8.19\""));
+        assert!(config.contains("optimizer_runs = 10000"));
+        assert!(config.contains("evm_version = \"london\""));
+        assert!(!config.contains("via_ir"));
+    }
+
+    #[test]
+    fn hardhat_project_test() -> Result<(), ParseError> {
+        let sources = [
+            ("contracts/Vault.sol", "import \"@openzeppelin/contracts/token/ERC20/ERC20.sol\";"),
+            ("@openzeppelin/contracts/token/ERC20/ERC20.sol", "// OpenZeppelin Contracts (last updated v4.9.0) (token/ERC20/ERC20.sol)"),
+            ("@openzeppelin/contracts/utils/Context.sol", "// OpenZeppelin Contracts v4.4.1 (utils/Context.sol)"),
+            ("@chainlink/contracts/src/v0.8/interfaces/AggregatorV3Interface.sol", "interface AggregatorV3Interface {}"),
+            ("@chainlink/contracts/src/v0.8/vendor/openzeppelin-solidity/v4.8.0/contracts/utils/Address.sol", "// OpenZeppelin Contracts (last updated v4.8.0) (utils/Address.sol)"),
+            ("@openzeppelin/contracts-upgradeable/proxy/utils/Initializable.sol", "// OpenZeppelin Contracts (last updated v4.9.0) (proxy/utils/Initializable.sol)"),
+            ("@local.sol", "")
+        ];
+        let dependencies = get_npm_dependencies(&sources);
+        assert_eq!(dependencies.len(), 3);
+        assert_eq!(dependencies["@openzeppelin/contracts-upgradeable"], "4.9.0");
+        assert_eq!(dependencies["@openzeppelin/contracts"], "4.9.0");
+        assert_eq!(dependencies["@chainlink/contracts"], "*");
+
+        let package: Value = serde_json::from_str(&get_package_json("0xABC", &sources)?)?;
+        assert_eq!(package["name"], "0xabc");
+        assert_eq!(package["dependencies"]["@openzeppelin/contracts"], "4.9.0");
+
+        let metadata = ContractMetadata {
+            compiler_version: "v0.8.19+commit.7dd6d404".to_owned(),
+            optimization_used: true,
+            runs: Some(200),
+            evm_version: Some("Paris".to_owned()),
+            ..Default::default()
+        };
+        let config = get_hardhat_config(&metadata, &Value::Null)?;
+        assert!(config.starts_with("/** @type"));
+        assert!(config.contains("\"version\": \"0.8.19\""));
+        assert!(config.contains("\"runs\": 200"));
+        assert!(config.contains("\"evmVersion\": \"paris\""));
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use std::str::FromStr;
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::{ApiDB, ChainConfig, ContractData, ContractMetadata, ParseError, ParseOptions, Parser};
+
+// source of verified contract code, providers are tried in configured order
+
+pub trait SourceProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn fetch<'a>(
+        &'a self,
+        chain: &'a ChainConfig,
+        contract_address: &'a str,
+        api: &'a ApiDB,
+        options: &'a ParseOptions
+    ) -> BoxFuture<'a, Result<ContractData, ParseError>>;
+}
+
+// provider names accepted in cli and options
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderKind {
+    Etherscan,
+    Sourcify
+}
+
+impl ProviderKind {
+    pub fn provider(&self) -> Box<dyn SourceProvider> {
+        match self {
+            ProviderKind::Etherscan => Box::new(EtherscanProvider),
+            ProviderKind::Sourcify => Box::new(SourcifyProvider)
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = ParseError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "etherscan" => Ok(ProviderKind::Etherscan),
+            "sourcify" => Ok(ProviderKind::Sourcify),
+            other => Err(ParseError::InvalidArgs(format!("unknown source provider \"{}\"", other)))
+        }
+    }
+}
+
+// etherscan-style getsourcecode api of chain explorer
+
+pub struct EtherscanProvider;
+
+impl SourceProvider for EtherscanProvider {
+    fn name(&self) -> &'static str {
+        "etherscan"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        chain: &'a ChainConfig,
+        contract_address: &'a str,
+        api: &'a ApiDB,
+        options: &'a ParseOptions
+    ) -> BoxFuture<'a, Result<ContractData, ParseError>> {
+        Box::pin(Parser::get_contract_data(chain, contract_address, api, options))
+    }
+}
+
+// sourcify repository, needs no key but chain id
+
+pub struct SourcifyProvider;
+
+impl SourcifyProvider {
+    pub const API_URL: &'static str = "https://sourcify.dev/server";
+
+    // single files request, full match is preferred by sourcify itself
+
+    async fn request_contract_data(chain_id: u64, contract_address: &str) -> Result<ContractData, ParseError> {
+        let url = format!("{}/files/any/{}/{}", SourcifyProvider::API_URL, chain_id, contract_address);
+        let response = reqwest::get(url).await?;
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => return Err(ParseError::Unverified(contract_address.to_owned())),
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                return Err(ParseError::RateLimited(format!("sourcify http status {}", response.status())))
+            }
+            status if !status.is_success() => {
+                return Err(ParseError::ExplorerStatus(format!("sourcify http status {}", status)))
+            }
+            _ => {}
+        }
+        let body = response.text().await?;
+        let files: SourcifyFiles = serde_json::from_str(&body)?;
+        files.into_contract_data(contract_address)
+    }
+}
+
+impl SourceProvider for SourcifyProvider {
+    fn name(&self) -> &'static str {
+        "sourcify"
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        chain: &'a ChainConfig,
+        contract_address: &'a str,
+        _api: &'a ApiDB,
+        options: &'a ParseOptions
+    ) -> BoxFuture<'a, Result<ContractData, ParseError>> {
+        Box::pin(async move {
+            let chain_id = chain.chain_id.ok_or_else(|| {
+                ParseError::ExplorerStatus(format!("sourcify needs chain id of chain \"{}\"", chain.name))
+            })?;
+            options.retry.run(|| SourcifyProvider::request_contract_data(chain_id, contract_address)).await
+        })
+    }
+}
+
+// files/any response, "status" is "full" or "partial"
+
+#[derive(Debug, Deserialize)]
+struct SourcifyFiles {
+    status: String,
+    files: Vec<SourcifyFile>
+}
+
+#[derive(Debug, Deserialize)]
+struct SourcifyFile {
+    name: String,
+    path: String,
+    content: String
+}
+
+impl SourcifyFiles {
+
+    // rebuild standard json input from metadata.json and source files
+
+    fn into_contract_data(self, contract_address: &str) -> Result<ContractData, ParseError> {
+        let metadata = self.files.iter()
+            .find(|file| file.name == "metadata.json")
+            .ok_or_else(|| ParseError::Json(format!("no metadata.json in sourcify files of {}", contract_address)))?;
+        let metadata: Value = serde_json::from_str(&metadata.content)?;
+
+        let name = metadata["settings"]["compilationTarget"].as_object()
+            .and_then(|target| target.values().next())
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_owned();
+
+        // source keys are the part of repository path after "/sources/"
+
+        let mut sources = Map::new();
+        for file in self.files.iter().filter(|file| file.name != "metadata.json") {
+            let key = match file.path.find("/sources/") {
+                Some(start) => &file.path[start + "/sources/".len()..],
+                None => file.name.as_str()
+            };
+            sources.insert(key.to_owned(), json!({ "content": file.content }));
+        }
+        if sources.is_empty() {
+            return Err(ParseError::Unverified(contract_address.to_owned()))
+        }
+
+        let libraries = metadata["settings"]["libraries"].as_object()
+            .filter(|libraries| !libraries.is_empty())
+            .map(|libraries| Value::Object(libraries.clone()).to_string());
+        let contract_metadata = ContractMetadata {
+            language: metadata["language"].as_str().unwrap_or("Solidity").to_owned(),
+            compiler_version: metadata["compiler"]["version"].as_str().unwrap_or_default().to_owned(),
+            optimization_used: metadata["settings"]["optimizer"]["enabled"].as_bool().unwrap_or(false),
+            runs: metadata["settings"]["optimizer"]["runs"].as_u64(),
+            evm_version: metadata["settings"]["evmVersion"].as_str().map(str::to_owned),
+            license_type: None,
+            constructor_arguments: None,
+            library: libraries
+        };
+        let abi = Some(metadata["output"]["abi"].clone()).filter(Value::is_array);
+
+        let mut settings = metadata["settings"].clone();
+        if let Some(settings) = settings.as_object_mut() {
+            settings.remove("compilationTarget");
+        }
+        let input = json!({
+            "language": metadata["language"],
+            "sources": sources,
+            "settings": settings
+        });
+
+        Ok(ContractData {
+            name,
+            code: input.to_string(),
+            provider: format!("sourcify ({} match)", self.status),
+            implementation: None,
+            metadata: contract_metadata,
+            abi
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn sourcify_files_test() -> Result<(), Box<dyn Error>> {
+        let files: SourcifyFiles = serde_json::from_str(r#"{
+            "status": "partial",
+            "files": [
+                {
+                    "name": "metadata.json",
+                    "path": "/data/repository/contracts/partial_match/1/0x0/metadata.json",
+                    "content": "{\"language\":\"Solidity\",\"compiler\":{\"version\":\"0.8.19+commit.7dd6d404\"},\"output\":{\"abi\":[]},\"settings\":{\"compilationTarget\":{\"contracts/Token.sol\":\"Token\"},\"evmVersion\":\"paris\",\"optimizer\":{\"enabled\":true,\"runs\":200}}}"
+                },
+                {
+                    "name": "Token.sol",
+                    "path": "/data/repository/contracts/partial_match/1/0x0/sources/contracts/Token.sol",
+                    "content": "contract Token {}"
+                }
+            ]
+        }"#)?;
+        let data = files.into_contract_data("0x0")?;
+        assert_eq!(data.name, "Token");
+        assert_eq!(data.provider, "sourcify (partial match)");
+        assert_eq!(data.metadata.compiler_version, "0.8.19+commit.7dd6d404");
+        assert_eq!(data.metadata.runs, Some(200));
+        assert_eq!(data.metadata.evm_version.as_deref(), Some("paris"));
+        assert!(data.metadata.optimization_used);
+        assert_eq!(data.abi, Some(Value::Array(Vec::new())));
+
+        let input: Value = serde_json::from_str(&data.code)?;
+        assert_eq!(input["language"], "Solidity");
+        assert_eq!(input["sources"]["contracts/Token.sol"]["content"], "contract Token {}");
+        assert_eq!(input["settings"]["optimizer"]["runs"], 200);
+        assert!(input["settings"]["compilationTarget"].is_null());
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+// token bucket refilled with requests per second budget of one api key
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    pub rps: f64,
+    bucket: Mutex<Bucket>
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant
+}
+
+impl RateLimiter {
+
+    // init with full bucket, burst is never less than one request
+
+    pub fn new(rps: f64) -> Self {
+        Self {
+            rps,
+            bucket: Mutex::new(Bucket {
+                tokens: rps.max(1.0),
+                last_refill: Instant::now()
+            })
+        }
+    }
+
+    // wait until the budget allows one more request
+
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.rps).min(self.rps.max(1.0));
+                bucket.last_refill = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.rps)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rate_limiter_test() {
+        let limiter = RateLimiter::new(100.0);
+        let start = Instant::now();
+
+        // burst of 100 goes through right away
+        for _ in 0..100 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // next 10 requests are spread over 100 ms
+        for _ in 0..10 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,71 @@
+use super::ChainConfig;
+
+// well-known explorers shipped with the binary, keys.json entries override them
+
+struct KnownChain {
+    name: &'static str,
+    hosts: &'static [&'static str],
+    api_url: &'static str,
+    chain_id: u64,
+    etherscan_v2: bool
+}
+
+const KNOWN_CHAINS: &[KnownChain] = &[
+    KnownChain { name: "etherscan", hosts: &["etherscan.io"], api_url: "https://api.etherscan.io", chain_id: 1, etherscan_v2: true },
+    KnownChain { name: "sepolia", hosts: &["sepolia.etherscan.io"], api_url: "https://api-sepolia.etherscan.io", chain_id: 11155111, etherscan_v2: true },
+    KnownChain { name: "goerli", hosts: &["goerli.etherscan.io"], api_url: "https://api-goerli.etherscan.io", chain_id: 5, etherscan_v2: false },
+    KnownChain { name: "optimistic", hosts: &["optimistic.etherscan.io"], api_url: "https://api-optimistic.etherscan.io", chain_id: 10, etherscan_v2: true },
+    KnownChain { name: "bscscan", hosts: &["bscscan.com"], api_url: "https://api.bscscan.com", chain_id: 56, etherscan_v2: true },
+    KnownChain { name: "polygonscan", hosts: &["polygonscan.com"], api_url: "https://api.polygonscan.com", chain_id: 137, etherscan_v2: true },
+    KnownChain { name: "zkevm", hosts: &["zkevm.polygonscan.com"], api_url: "https://api-zkevm.polygonscan.com", chain_id: 1101, etherscan_v2: true },
+    KnownChain { name: "arbiscan", hosts: &["arbiscan.io"], api_url: "https://api.arbiscan.io", chain_id: 42161, etherscan_v2: true },
+    KnownChain { name: "nova", hosts: &["nova.arbiscan.io"], api_url: "https://api-nova.arbiscan.io", chain_id: 42170, etherscan_v2: true },
+    KnownChain { name: "snowtrace", hosts: &["snowtrace.io"], api_url: "https://api.snowtrace.io", chain_id: 43114, etherscan_v2: true },
+    KnownChain { name: "ftmscan", hosts: &["ftmscan.com"], api_url: "https://api.ftmscan.com", chain_id: 250, etherscan_v2: false },
+    KnownChain { name: "basescan", hosts: &["basescan.org"], api_url: "https://api.basescan.org", chain_id: 8453, etherscan_v2: true },
+    KnownChain { name: "gnosisscan", hosts: &["gnosisscan.io"], api_url: "https://api.gnosisscan.io", chain_id: 100, etherscan_v2: true },
+    KnownChain { name: "celoscan", hosts: &["celoscan.io"], api_url: "https://api.celoscan.io", chain_id: 42220, etherscan_v2: true },
+    KnownChain { name: "moonbeam", hosts: &["moonbeam.moonscan.io"], api_url: "https://api-moonbeam.moonscan.io", chain_id: 1284, etherscan_v2: true },
+    KnownChain { name: "moonriver", hosts: &["moonriver.moonscan.io"], api_url: "https://api-moonriver.moonscan.io", chain_id: 1285, etherscan_v2: true },
+    KnownChain { name: "cronoscan", hosts: &["cronoscan.com"], api_url: "https://api.cronoscan.com", chain_id: 25, etherscan_v2: true },
+    KnownChain { name: "lineascan", hosts: &["lineascan.build"], api_url: "https://api.lineascan.build", chain_id: 59144, etherscan_v2: true },
+    KnownChain { name: "scrollscan", hosts: &["scrollscan.com"], api_url: "https://api.scrollscan.com", chain_id: 534352, etherscan_v2: true },
+    KnownChain { name: "blastscan", hosts: &["blastscan.io"], api_url: "https://api.blastscan.io", chain_id: 81457, etherscan_v2: true },
+    KnownChain { name: "blockscout-eth", hosts: &["eth.blockscout.com"], api_url: "https://eth.blockscout.com", chain_id: 1, etherscan_v2: false },
+    KnownChain { name: "blockscout-gnosis", hosts: &["gnosis.blockscout.com"], api_url: "https://gnosis.blockscout.com", chain_id: 100, etherscan_v2: false },
+    KnownChain { name: "blockscout-optimism", hosts: &["optimism.blockscout.com"], api_url: "https://optimism.blockscout.com", chain_id: 10, etherscan_v2: false },
+    KnownChain { name: "blockscout-base", hosts: &["base.blockscout.com"], api_url: "https://base.blockscout.com", chain_id: 8453, etherscan_v2: false },
+    KnownChain { name: "blockscout-arbitrum", hosts: &["arbitrum.blockscout.com"], api_url: "https://arbitrum.blockscout.com", chain_id: 42161, etherscan_v2: false },
+    KnownChain { name: "blockscout-zkevm", hosts: &["zkevm.blockscout.com"], api_url: "https://zkevm.blockscout.com", chain_id: 1101, etherscan_v2: false },
+    KnownChain { name: "zora", hosts: &["explorer.zora.energy"], api_url: "https://explorer.zora.energy", chain_id: 7777777, etherscan_v2: false }
+];
+
+// all built-in chains without keys
+
+pub fn known_chains() -> Vec<ChainConfig> {
+    KNOWN_CHAINS.iter().map(KnownChain::to_config).collect()
+}
+
+// built-in chain by name
+
+pub fn known_chain(name: &str) -> Option<ChainConfig> {
+    KNOWN_CHAINS.iter()
+        .find(|chain| chain.name == name)
+        .map(KnownChain::to_config)
+}
+
+impl KnownChain {
+    fn to_config(&self) -> ChainConfig {
+        ChainConfig {
+            name: self.name.to_owned(),
+            hosts: self.hosts.iter().map(|host| host.to_string()).collect(),
+            api_url: self.api_url.to_owned(),
+            api_keys: Vec::new(),
+            chain_id: Some(self.chain_id),
+            etherscan_v2: self.etherscan_v2,
+            rpc_url: None,
+            rate_limit: ChainConfig::DEFAULT_RATE_LIMIT,
+            enabled: true
+        }
+    }
+}
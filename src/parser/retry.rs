@@ -0,0 +1,107 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::ParseError;
+
+// exponential backoff with full jitter for explorer requests
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30)
+        }
+    }
+}
+
+impl RetryPolicy {
+
+    // policy with default delays and custom attempts count
+
+    pub fn with_attempts(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            ..Self::default()
+        }
+    }
+
+    // only rate limits and transport failures are worth another try
+
+    fn is_retryable(why: &ParseError) -> bool {
+        matches!(why, ParseError::RateLimited(_) | ParseError::Network(_))
+    }
+
+    // random delay between zero and the exponential cap of this attempt
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let millis = exponential.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+    }
+
+    // run request until it succeeds, fails with non retryable error or attempts are over
+
+    pub async fn run<T, F, Fut>(&self, mut request: F) -> Result<T, ParseError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ParseError>>
+    {
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(why) if Self::is_retryable(&why) && attempt + 1 < self.max_attempts => {
+                    let delay = self.delay(attempt);
+                    eprintln!("{}, retrying in {} ms", why, delay.as_millis());
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn retry_policy_test() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2)
+        };
+
+        // rate limit is retried until attempts are over
+        let attempts = Cell::new(0);
+        let result: Result<(), ParseError> = policy.run(|| async {
+            attempts.set(attempts.get() + 1);
+            Err(ParseError::RateLimited("Max rate limit reached".to_owned()))
+        }).await;
+        assert!(matches!(result, Err(ParseError::RateLimited(_))));
+        assert_eq!(attempts.get(), 3);
+
+        // other errors are returned right away
+        let attempts = Cell::new(0);
+        let result: Result<(), ParseError> = policy.run(|| async {
+            attempts.set(attempts.get() + 1);
+            Err(ParseError::Unverified("0x0".to_owned()))
+        }).await;
+        assert!(matches!(result, Err(ParseError::Unverified(_))));
+        assert_eq!(attempts.get(), 1);
+    }
+}
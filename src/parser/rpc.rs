@@ -0,0 +1,291 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::ParseError;
+
+// bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)
+
+const EIP1967_IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+
+// bytes32(uint256(keccak256("eip1967.proxy.beacon")) - 1)
+
+const EIP1967_BEACON_SLOT: &str = "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+
+// bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)
+
+const EIP1967_ADMIN_SLOT: &str = "0xb53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103";
+
+// keccak256("PROXIABLE")
+
+const EIP1822_PROXIABLE_SLOT: &str = "0xc5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7";
+
+// implementation() selector of beacon
+
+const BEACON_IMPLEMENTATION_SELECTOR: &str = "0x5c60da1b";
+
+// runtime code of eip-1167 minimal proxy around 20 bytes of implementation address
+
+const EIP1167_PREFIX: &str = "363d3d373d3d3d363d73";
+const EIP1167_SUFFIX: &str = "5af43d82803e903d91602b57fd5bf3";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyKind {
+    Eip1167,
+    Eip1967,
+    Eip1967Beacon,
+    Eip1822
+}
+
+// implementation found in proxy storage or bytecode
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedProxy {
+    pub kind: ProxyKind,
+    pub implementation: String,
+    pub admin: Option<String>,
+    pub beacon: Option<String>
+}
+
+// reads proxy slots through json-rpc endpoint of chain
+
+pub struct ProxyResolver<'a> {
+    rpc_url: &'a str
+}
+
+impl<'a> ProxyResolver<'a> {
+    pub fn new(rpc_url: &'a str) -> Self {
+        Self { rpc_url }
+    }
+
+    // single json-rpc call
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value, ParseError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params
+        });
+        let response = reqwest::Client::new()
+            .post(self.rpc_url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(request.to_string())
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(ParseError::RateLimited(format!("rpc http status {}", response.status())))
+        }
+        let body = response.text().await?;
+        let mut json: Value = serde_json::from_str(&body)?;
+        if let Some(error) = json.get("error") {
+            return Err(ParseError::ExplorerStatus(format!("rpc {} failed: {}", method, error)))
+        }
+        match json.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(ParseError::Json(format!("rpc {} response without result", method)))
+        }
+    }
+
+    // address stored in the low 20 bytes of a 32 bytes word, zero means none
+
+    fn word_to_address(word: &str) -> Option<String> {
+        let hex = word.trim_start_matches("0x");
+        if hex.len() < 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None
+        }
+        let address = &hex[hex.len() - 40..];
+        match address.chars().all(|c| c == '0') {
+            true => None,
+            false => Some(format!("0x{}", address.to_lowercase()))
+        }
+    }
+
+    async fn get_storage_address(&self, contract_address: &str, slot: &str) -> Result<Option<String>, ParseError> {
+        let word = self.call("eth_getStorageAt", json!([contract_address, slot, "latest"])).await?;
+        Ok(word.as_str().and_then(ProxyResolver::word_to_address))
+    }
+
+    // implementation address embedded into eip-1167 clone bytecode
+
+    async fn get_minimal_proxy_implementation(&self, contract_address: &str) -> Result<Option<String>, ParseError> {
+        let code = self.call("eth_getCode", json!([contract_address, "latest"])).await?;
+        let code = code.as_str().unwrap_or_default().trim_start_matches("0x").to_lowercase();
+        let implementation = code.strip_prefix(EIP1167_PREFIX)
+            .and_then(|rest| rest.strip_suffix(EIP1167_SUFFIX))
+            .filter(|address| address.len() == 40)
+            .map(|address| format!("0x{}", address));
+        Ok(implementation)
+    }
+
+    // find implementation of proxy, none if contract doesn't look like a proxy
+
+    pub async fn resolve(&self, contract_address: &str) -> Result<Option<ResolvedProxy>, ParseError> {
+        if let Some(implementation) = self.get_minimal_proxy_implementation(contract_address).await? {
+            return Ok(Some(ResolvedProxy {
+                kind: ProxyKind::Eip1167,
+                implementation,
+                admin: None,
+                beacon: None
+            }))
+        }
+
+        let admin = self.get_storage_address(contract_address, EIP1967_ADMIN_SLOT).await?;
+
+        if let Some(implementation) = self.get_storage_address(contract_address, EIP1967_IMPLEMENTATION_SLOT).await? {
+            return Ok(Some(ResolvedProxy {
+                kind: ProxyKind::Eip1967,
+                implementation,
+                admin,
+                beacon: None
+            }))
+        }
+
+        if let Some(beacon) = self.get_storage_address(contract_address, EIP1967_BEACON_SLOT).await? {
+            let word = self.call("eth_call", json!([
+                { "to": beacon, "data": BEACON_IMPLEMENTATION_SELECTOR },
+                "latest"
+            ])).await?;
+            if let Some(implementation) = word.as_str().and_then(ProxyResolver::word_to_address) {
+                return Ok(Some(ResolvedProxy {
+                    kind: ProxyKind::Eip1967Beacon,
+                    implementation,
+                    admin,
+                    beacon: Some(beacon)
+                }))
+            }
+        }
+
+        if let Some(implementation) = self.get_storage_address(contract_address, EIP1822_PROXIABLE_SLOT).await? {
+            return Ok(Some(ResolvedProxy {
+                kind: ProxyKind::Eip1822,
+                implementation,
+                admin,
+                beacon: None
+            }))
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const IMPLEMENTATION: &str = "0x5041ed759dd4afc3a72b8192c143f72f4724081a";
+    const BEACON: &str = "0x1111111111111111111111111111111111111111";
+    const ZERO_WORD: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+    // answers json-rpc calls of one mocked proxy until test ends
+
+    async fn mock_rpc(answer: fn(&str, &Value) -> Value) -> Result<String, Box<dyn Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+
+                // read headers and body of content-length
+
+                loop {
+                    let read = socket.read(&mut buf).await.unwrap_or(0);
+                    if read == 0 {
+                        break
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(headers_end) = text.find("\r\n\r\n") {
+                        let length = text[..headers_end].lines()
+                            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|len| len.trim().to_owned()))
+                            .and_then(|len| len.parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if request.len() >= headers_end + 4 + length {
+                            break
+                        }
+                    }
+                }
+
+                let text = String::from_utf8_lossy(&request).to_string();
+                let body = &text[text.find("\r\n\r\n").map_or(0, |end| end + 4)..];
+                let call: Value = serde_json::from_str(body).unwrap_or_default();
+                let result = answer(call["method"].as_str().unwrap_or_default(), &call["params"]);
+                let response = json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string();
+                let http = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                );
+                let _ = socket.write_all(http.as_bytes()).await;
+            }
+        });
+        Ok(url)
+    }
+
+    fn address_word(address: &str) -> Value {
+        json!(format!("0x{:0>64}", address.trim_start_matches("0x")))
+    }
+
+    #[tokio::test]
+    async fn eip1967_proxy_test() -> Result<(), Box<dyn Error>> {
+        let url = mock_rpc(|method, params| match method {
+            "eth_getCode" => json!("0x6080604052"),
+            "eth_getStorageAt" if params[1] == EIP1967_IMPLEMENTATION_SLOT => address_word(IMPLEMENTATION),
+            _ => json!(ZERO_WORD)
+        }).await?;
+        let resolved = ProxyResolver::new(&url).resolve("0xdac17f958d2ee523a2206206994597c13d831ec7").await?;
+        assert_eq!(resolved, Some(ResolvedProxy {
+            kind: ProxyKind::Eip1967,
+            implementation: IMPLEMENTATION.to_owned(),
+            admin: None,
+            beacon: None
+        }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn beacon_proxy_test() -> Result<(), Box<dyn Error>> {
+        let url = mock_rpc(|method, params| match method {
+            "eth_getCode" => json!("0x6080604052"),
+            "eth_getStorageAt" if params[1] == EIP1967_BEACON_SLOT => address_word(BEACON),
+            "eth_call" if params[0]["to"] == BEACON => address_word(IMPLEMENTATION),
+            _ => json!(ZERO_WORD)
+        }).await?;
+        let resolved = ProxyResolver::new(&url).resolve("0xdac17f958d2ee523a2206206994597c13d831ec7").await?.unwrap();
+        assert_eq!(resolved.kind, ProxyKind::Eip1967Beacon);
+        assert_eq!(resolved.implementation, IMPLEMENTATION);
+        assert_eq!(resolved.beacon.as_deref(), Some(BEACON));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn eip1822_and_minimal_proxy_test() -> Result<(), Box<dyn Error>> {
+        let url = mock_rpc(|method, params| match method {
+            "eth_getCode" => json!("0x6080604052"),
+            "eth_getStorageAt" if params[1] == EIP1822_PROXIABLE_SLOT => address_word(IMPLEMENTATION),
+            _ => json!(ZERO_WORD)
+        }).await?;
+        let resolved = ProxyResolver::new(&url).resolve("0xdac17f958d2ee523a2206206994597c13d831ec7").await?.unwrap();
+        assert_eq!(resolved.kind, ProxyKind::Eip1822);
+
+        let url = mock_rpc(|method, _| match method {
+            "eth_getCode" => json!(format!("0x{}{}{}", EIP1167_PREFIX, IMPLEMENTATION.trim_start_matches("0x"), EIP1167_SUFFIX)),
+            _ => json!(ZERO_WORD)
+        }).await?;
+        let resolved = ProxyResolver::new(&url).resolve("0xdac17f958d2ee523a2206206994597c13d831ec7").await?.unwrap();
+        assert_eq!(resolved.kind, ProxyKind::Eip1167);
+        assert_eq!(resolved.implementation, IMPLEMENTATION);
+
+        // not a proxy
+        let url = mock_rpc(|method, _| match method {
+            "eth_getCode" => json!("0x6080604052"),
+            _ => json!(ZERO_WORD)
+        }).await?;
+        assert_eq!(ProxyResolver::new(&url).resolve("0xdac17f958d2ee523a2206206994597c13d831ec7").await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::ParseError;
+
+// run manifest kept in output folder of parse_imm, so reruns continue where the last one stopped
+
+pub const RUN_STATE_FILE: &str = "run.json";
+
+const RUN_STATE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Pending,
+    Ok,
+    Failed,
+    Skipped
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UrlState {
+    pub status: RunStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: u64
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunState {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub urls: BTreeMap<String, UrlState>
+}
+
+// seconds since unix epoch
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+impl RunState {
+
+    // state of previous run, empty if folder has none
+
+    pub async fn load(folder: &Path) -> Result<Self, ParseError> {
+        match fs::read_to_string(folder.join(RUN_STATE_FILE)).await {
+            Ok(json_str) => Ok(serde_json::from_str(&json_str)?),
+            Err(why) if why.kind() == std::io::ErrorKind::NotFound => Ok(RunState::default()),
+            Err(why) => Err(why.into())
+        }
+    }
+
+    // written to temporary file first, so a crash never leaves half of run.json
+
+    pub async fn save(&self, folder: &Path) -> Result<(), ParseError> {
+        fs::create_dir_all(folder).await?;
+        let stored = RunState {
+            version: RUN_STATE_VERSION,
+            urls: self.urls.clone()
+        };
+        let temp_path = folder.join(format!("{}.tmp", RUN_STATE_FILE));
+        fs::write(&temp_path, serde_json::to_string_pretty(&stored)?).await?;
+        fs::rename(&temp_path, folder.join(RUN_STATE_FILE)).await?;
+        Ok(())
+    }
+
+    // contracts parsed successfully before are not fetched again
+
+    pub fn is_done(&self, url: &str) -> bool {
+        self.urls.get(url).is_some_and(|state| state.status == RunStatus::Ok)
+    }
+
+    pub fn set(&mut self, url: &str, status: RunStatus, source_hash: Option<String>, error: Option<String>) {
+        self.urls.insert(url.to_owned(), UrlState {
+            status,
+            source_hash,
+            error,
+            timestamp: now()
+        });
+    }
+
+    pub fn count(&self, status: RunStatus) -> usize {
+        self.urls.values().filter(|state| state.status == status).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn run_state_test() -> Result<(), Box<dyn std::error::Error>> {
+        let folder = std::env::temp_dir().join(format!("iscp-run-state-{}", std::process::id()));
+        let mut state = RunState::load(&folder).await?;
+        assert!(state.urls.is_empty());
+
+        state.set("https://etherscan.io/address/0x1", RunStatus::Ok, Some("abc".to_owned()), None);
+        state.set("https://etherscan.io/address/0x2", RunStatus::Failed, None, Some("rate limit".to_owned()));
+        state.set("https://etherscan.io/address/0x3", RunStatus::Pending, None, None);
+        state.save(&folder).await?;
+
+        let state = RunState::load(&folder).await?;
+        assert_eq!(state.version, RUN_STATE_VERSION);
+        assert!(state.is_done("https://etherscan.io/address/0x1"));
+        assert!(!state.is_done("https://etherscan.io/address/0x2"));
+        assert!(!state.is_done("https://etherscan.io/address/0x4"));
+        assert_eq!(state.urls["https://etherscan.io/address/0x1"].source_hash.as_deref(), Some("abc"));
+        assert_eq!(state.count(RunStatus::Failed), 1);
+        assert_eq!(state.count(RunStatus::Pending), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(folder.join(RUN_STATE_FILE)).await?)?;
+        assert_eq!(json["urls"]["https://etherscan.io/address/0x2"]["status"], "failed");
+        assert!(fs::metadata(folder.join("run.json.tmp")).await.is_err());
+        fs::remove_dir_all(&folder).await?;
+        Ok(())
+    }
+}
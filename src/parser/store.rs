@@ -0,0 +1,110 @@
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use super::ParseError;
+
+// shared folder of one run where identical sources are kept once as lib/<hash>/<file name>
+
+pub const LIB_DIR: &str = "lib";
+
+// first 128 bits of sha-256, enough to tell source files apart
+
+pub fn get_content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// store content once and point file_path to it with a relative symlink, so the output
+// folder can be moved, returns the stored file
+
+pub async fn save_deduplicated(root: &Path, file_path: &Path, content: &str) -> Result<PathBuf, ParseError> {
+    let unsafe_path = || ParseError::UnsafePath(file_path.display().to_string());
+    let name = file_path.file_name().ok_or_else(unsafe_path)?;
+    let hash = get_content_hash(content);
+    let stored = root.join(LIB_DIR).join(&hash).join(name);
+    if fs::metadata(&stored).await.is_err() {
+        fs::create_dir_all(root.join(LIB_DIR).join(&hash)).await?;
+        fs::write(&stored, content).await?;
+    }
+
+    // "../" for every folder between root and the link
+
+    let link_dir = file_path.parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+        .ok_or_else(unsafe_path)?;
+    let mut target = PathBuf::new();
+    for component in link_dir.components() {
+        match component {
+            Component::Normal(_) => target.push(".."),
+            Component::CurDir => {}
+            _ => return Err(unsafe_path())
+        }
+    }
+    let target = target.join(LIB_DIR).join(&hash).join(name);
+
+    // file or link of previous run is replaced
+
+    fs::create_dir_all(file_path.parent().ok_or_else(unsafe_path)?).await?;
+    if fs::symlink_metadata(file_path).await.is_ok() {
+        fs::remove_file(file_path).await?;
+    }
+    link(&target, file_path, content).await?;
+    Ok(stored)
+}
+
+#[cfg(unix)]
+async fn link(target: &Path, file_path: &Path, _content: &str) -> Result<(), ParseError> {
+    fs::symlink(target, file_path).await?;
+    Ok(())
+}
+
+// symlinks need extra privileges elsewhere, so the file is copied
+
+#[cfg(not(unix))]
+async fn link(_target: &Path, file_path: &Path, content: &str) -> Result<(), ParseError> {
+    fs::write(file_path, content).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[tokio::test]
+    async fn deduplicated_store_test() -> Result<(), Box<dyn Error>> {
+        let root = std::env::temp_dir().join(format!("iscp-store-{}", std::process::id()));
+        let content = "library SafeMath {}";
+        let first = root.join("0x1").join("@openzeppelin/contracts/math/SafeMath.sol");
+        let second = root.join("0x2").join("src/libraries/SafeMath.sol");
+
+        let stored = save_deduplicated(&root, &first, content).await?;
+        assert_eq!(save_deduplicated(&root, &second, content).await?, stored);
+        // rerun replaces the link
+        save_deduplicated(&root, &second, content).await?;
+
+        assert_eq!(stored, root.join(LIB_DIR).join(get_content_hash(content)).join("SafeMath.sol"));
+        assert_eq!(fs::read_to_string(&first).await?, content);
+        assert_eq!(fs::read_to_string(&second).await?, content);
+        let mut entries = fs::read_dir(root.join(LIB_DIR)).await?;
+        let mut count = 0;
+        while entries.next_entry().await?.is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 1);
+
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(&first).await?,
+            PathBuf::from("../../../../lib").join(get_content_hash(content)).join("SafeMath.sol")
+        );
+
+        assert_ne!(get_content_hash(content), get_content_hash("library SafeMath { }"));
+        fs::remove_dir_all(&root).await?;
+        Ok(())
+    }
+}